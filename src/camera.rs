@@ -0,0 +1,293 @@
+// Responsible for choosing how particles are seen: the population is rotated in its DIMENSIONS dimensions, then its
+// first two rotated coordinates are zoomed and shifted onto the screen. With a perspective, the third rotated
+// coordinate is the depth, farther particles being drawn smaller and closer to the center.
+
+use crate::physics::{Coordinates, DIMENSIONS};
+
+/// Angle in radians of a single rotation step
+pub const ROTATION_STEP: f64 = std::f64::consts::PI / 90f64;
+
+/// Axis of the rotated space pointing away from the viewer
+const DEPTH_AXIS: usize = 2;
+
+/// Share of the look at distance under which particles are too close to the viewer to be drawn, as their size would
+/// grow without limit
+const NEAR_PLANE: f64 = 0.01;
+
+/// Viewer standing at `distance` from `look_at`, on the near side of the depth axis. Rotating the camera therefore
+/// orbits around `look_at`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Perspective {
+    pub look_at: Coordinates,
+    pub distance: f64,
+    /// Vertical field of view in radians
+    pub field_of_view: f64,
+}
+
+impl Perspective {
+    /// Looking at the origin from the distance where it is drawn at the same scale as without perspective
+    pub fn new(field_of_view: f64, height: usize) -> Self {
+        Perspective {
+            look_at: Coordinates::default(),
+            distance: Self::focal_length(field_of_view, height),
+            field_of_view,
+        }
+    }
+
+    // Distance at which one unit of length is one pixel
+    fn focal_length(field_of_view: f64, height: usize) -> f64 {
+        height as f64 / 2f64 / (field_of_view / 2f64).tan()
+    }
+
+    // Screen coordinates before zoom and shift, depth and scale of the point at `x`, `y`, `z` from `look_at` along
+    // the camera axes, the depth axis being the last. None if it is closer than the near plane.
+    fn project(&self, [x, y, z]: [f64; 3], height: usize) -> Option<(f64, f64, f64, f64)> {
+        let depth = z + self.distance;
+        if depth < NEAR_PLANE * self.distance {
+            return None;
+        }
+        let focal_length = Self::focal_length(self.field_of_view, height);
+        Some((
+            x * focal_length / depth,
+            y * focal_length / depth,
+            depth,
+            self.distance / depth,
+        ))
+    }
+}
+
+/// Where a position lands on the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projected {
+    pub x: isize,
+    pub y: isize,
+    /// Larger when farther from the viewer, only meaningful to sort particles
+    pub depth: f64,
+    /// Size relative to something at the look at distance, 1 without perspective
+    pub scale: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// Orthonormal rows, the first two being the screen axes
+    pub rotation: [[f64; DIMENSIONS]; DIMENSIONS],
+    pub zoom: f64,
+    pub shift: (isize, isize),
+    /// Plane `(i, j)` with `i < j` of the rotated space that `rotate` turns in
+    pub plane: (usize, usize),
+    /// None for an orthographic projection. Requires at least 3 dimensions.
+    pub perspective: Option<Perspective>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        let mut rotation = [[0f64; DIMENSIONS]; DIMENSIONS];
+        for (i, row) in rotation.iter_mut().enumerate() {
+            row[i] = 1f64;
+        }
+        Camera {
+            rotation,
+            zoom: 1.0,
+            shift: (0, 0),
+            plane: (0, 1),
+            perspective: None,
+        }
+    }
+}
+
+impl Camera {
+    /// Coordinate of `position` along the `axis` row of the rotation
+    pub fn rotated(&self, position: &Coordinates, axis: usize) -> f64 {
+        (0..DIMENSIONS).map(|i| self.rotation[axis][i] * position[i]).sum()
+    }
+
+    /// Where the viewer stands, only defined with a perspective
+    pub fn position(&self) -> Option<Coordinates> {
+        let perspective = self.perspective?;
+        let depth_axis = self.rotation.get(DEPTH_AXIS)?;
+        let mut position = perspective.look_at;
+        for i in 0..DIMENSIONS {
+            position[i] -= perspective.distance * depth_axis[i];
+        }
+        Some(position)
+    }
+
+    /// None if `position` is behind the viewer or closer than the near plane
+    pub fn project(&self, position: &Coordinates, width: usize, height: usize) -> Option<Projected> {
+        let (screen_x, screen_y, depth, scale) = match self.perspective {
+            Some(perspective) if DIMENSIONS > DEPTH_AXIS => {
+                let mut relative = *position;
+                for i in 0..DIMENSIONS {
+                    relative[i] -= perspective.look_at[i];
+                }
+                let axes = [0, 1, DEPTH_AXIS].map(|axis| self.rotated(&relative, axis));
+                perspective.project(axes, height)?
+            }
+            _ => {
+                let depth = if DIMENSIONS > DEPTH_AXIS {
+                    self.rotated(position, DEPTH_AXIS)
+                } else {
+                    0f64
+                };
+                (self.rotated(position, 0), self.rotated(position, 1), depth, 1f64)
+            }
+        };
+        Some(Projected {
+            x: (screen_x * self.zoom + (width as f64 / 2f64)) as isize + self.shift.0,
+            y: (screen_y * self.zoom + (height as f64 / 2f64)) as isize + self.shift.1,
+            depth,
+            scale,
+        })
+    }
+
+    /// Turn by `angle` radians in the selected plane
+    pub fn rotate(&mut self, angle: f64) {
+        let (i, j) = self.plane;
+        let (sin, cos) = angle.sin_cos();
+        let (row_i, row_j) = (self.rotation[i], self.rotation[j]);
+        for k in 0..DIMENSIONS {
+            self.rotation[i][k] = cos * row_i[k] - sin * row_j[k];
+            self.rotation[j][k] = sin * row_i[k] + cos * row_j[k];
+        }
+        self.orthonormalize();
+    }
+
+    // Gram-Schmidt, so that rounding errors do not pile up over many rotations
+    fn orthonormalize(&mut self) {
+        for i in 0..DIMENSIONS {
+            for j in 0..i {
+                let dot: f64 = (0..DIMENSIONS).map(|k| self.rotation[i][k] * self.rotation[j][k]).sum();
+                for k in 0..DIMENSIONS {
+                    self.rotation[i][k] -= dot * self.rotation[j][k];
+                }
+            }
+            let norm = self.rotation[i].iter().map(|x| x * x).sum::<f64>().sqrt();
+            for k in 0..DIMENSIONS {
+                self.rotation[i][k] /= norm;
+            }
+        }
+    }
+
+    /// Select the next plane to rotate in, going through every pair of axes
+    pub fn next_plane(&mut self) {
+        let (i, j) = self.plane;
+        self.plane = if j + 1 < DIMENSIONS {
+            (i, j + 1)
+        } else if i + 2 < DIMENSIONS {
+            (i + 1, i + 2)
+        } else {
+            (0, 1)
+        };
+    }
+
+    /// Show the next axes on screen, which for an unrotated camera moves from axes (0, 1) to (1, 2) and so on
+    pub fn cycle_axes(&mut self) {
+        self.rotation.rotate_left(1);
+    }
+
+    /// Back to the unrotated axes, leaving zoom and shift alone
+    pub fn reset_rotation(&mut self) {
+        self.rotation = Camera::default().rotation;
+    }
+
+    /// Back to the initial view, keeping the kind of projection
+    pub fn reset(&mut self) {
+        *self = Camera {
+            perspective: self.perspective,
+            ..Camera::default()
+        };
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::camera::{Camera, Perspective};
+    use crate::physics::diagnostics::PLANES;
+    use crate::physics::test::coordinates;
+    use crate::physics::{Coordinates, DIMENSIONS};
+
+    #[test]
+    fn test_camera() {
+        let mut camera = Camera::default();
+        let position = coordinates([10f64, 0f64]);
+        let screen_position = |camera: &Camera| {
+            let projected = camera.project(&position, 100, 100).unwrap();
+            (projected.x, projected.y)
+        };
+        assert_eq!(screen_position(&camera), (60, 50));
+
+        camera.rotate(std::f64::consts::FRAC_PI_2);
+        assert_eq!(screen_position(&camera), (50, 60));
+
+        camera.cycle_axes();
+        assert_eq!(screen_position(&camera), (60, 50));
+
+        // Going through every plane leads back to the first one
+        for _ in 0..PLANES {
+            camera.next_plane();
+        }
+        assert_eq!(camera.plane, (0, 1));
+        camera.reset_rotation();
+        assert_eq!(camera, Camera::default());
+    }
+
+    // Math of the perspective itself, which does not depend on the number of dimensions of the build
+    #[test]
+    fn test_perspective_projection() {
+        let perspective = Perspective::new(std::f64::consts::FRAC_PI_2, 100);
+        assert_eq!(perspective.distance.round(), 50f64);
+        let (near_x, _, near_depth, near_scale) = perspective.project([10f64, 0f64, -25f64], 100).unwrap();
+        assert!((near_x - 20f64).abs() < 1e-9 && (near_scale - 2f64).abs() < 1e-9);
+        let (far_x, _, far_depth, far_scale) = perspective.project([10f64, 0f64, 50f64], 100).unwrap();
+        assert!((far_x - 5f64).abs() < 1e-9 && (far_scale - 0.5).abs() < 1e-9);
+        assert!(near_depth < far_depth);
+        assert_eq!(perspective.project([0f64, 0f64, -60f64], 100), None);
+        // Just in front of the viewer, where sizes would blow up
+        assert_eq!(perspective.project([0f64, 0f64, -49.9], 100), None);
+        let wide = Perspective {
+            distance: 200f64,
+            ..perspective
+        };
+        assert!((wide.project([10f64, 0f64, 0f64], 100).unwrap().0 - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_perspective() {
+        let mut camera = Camera {
+            perspective: Some(Perspective::new(std::f64::consts::FRAC_PI_2, 100)),
+            ..Camera::default()
+        };
+        let at_depth = |x: f64, z: f64| {
+            let mut position = Coordinates::default();
+            position[0] = x;
+            if DIMENSIONS >= 3 {
+                position[2] = z;
+            }
+            position
+        };
+
+        if DIMENSIONS < 3 {
+            // Without a third axis there is no depth, the perspective being ignored
+            let projected = camera.project(&at_depth(10f64, 0f64), 100, 100).unwrap();
+            assert_eq!((projected.x, projected.y, projected.scale), (60, 50, 1f64));
+            assert_eq!(camera.position(), None);
+        } else {
+            let near = camera.project(&at_depth(10f64, -25f64), 100, 100).unwrap();
+            let far = camera.project(&at_depth(10f64, 50f64), 100, 100).unwrap();
+            assert_eq!((near.x, near.scale.round()), (70, 2f64));
+            assert_eq!(far.x, 55);
+            assert!((far.scale - 0.5).abs() < 1e-9);
+            assert!(near.depth < far.depth);
+            // Behind the viewer
+            assert_eq!(camera.project(&at_depth(0f64, -60f64), 100, 100), None);
+
+            // Orbiting half a turn around the look at point swaps near and far
+            camera.plane = (0, 2);
+            camera.rotate(std::f64::consts::PI);
+            assert!(camera.project(&at_depth(10f64, -25f64), 100, 100).unwrap().depth > 50f64);
+            assert_eq!(camera.position().unwrap()[2].round(), 50f64);
+            camera.reset();
+            assert_eq!(camera.position().unwrap()[2].round(), -50f64);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+// Responsible for choosing the color of each particle, either at random or by mapping a quantity such as its speed
+// through a colormap. The range of the quantity is taken from the current population at every frame, so that colors
+// keep spanning the whole colormap as the simulation evolves.
+
+use crate::physics::{Coordinates, DIMENSIONS, Particle, Population, distance_squared};
+use crate::render::Color;
+use rand::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Colormap {
+    /// Perceptually uniform, from dark blue to yellow
+    Viridis,
+    /// Perceptually uniform, from black to pale yellow through red
+    Inferno,
+    /// From blue to red through white, the middle of the range standing out
+    Diverging,
+}
+
+// Colors evenly spread from 0 to 1, linearly interpolated in between
+const VIRIDIS: [[u8; 3]; 9] = [
+    [0x44, 0x01, 0x54],
+    [0x48, 0x28, 0x78],
+    [0x3e, 0x4a, 0x89],
+    [0x31, 0x68, 0x8e],
+    [0x26, 0x82, 0x8e],
+    [0x1f, 0x9e, 0x89],
+    [0x35, 0xb7, 0x79],
+    [0x6d, 0xcd, 0x59],
+    [0xfd, 0xe7, 0x25],
+];
+const INFERNO: [[u8; 3]; 9] = [
+    [0x00, 0x00, 0x04],
+    [0x1f, 0x0c, 0x48],
+    [0x55, 0x0f, 0x6d],
+    [0x88, 0x22, 0x6a],
+    [0xba, 0x36, 0x55],
+    [0xe3, 0x59, 0x33],
+    [0xf9, 0x8c, 0x0a],
+    [0xf9, 0xc9, 0x32],
+    [0xfc, 0xff, 0xa4],
+];
+const DIVERGING: [[u8; 3]; 9] = [
+    [0x21, 0x66, 0xac],
+    [0x43, 0x93, 0xc3],
+    [0x92, 0xc5, 0xde],
+    [0xd1, 0xe5, 0xf0],
+    [0xf7, 0xf7, 0xf7],
+    [0xfd, 0xdb, 0xc7],
+    [0xf4, 0xa5, 0x82],
+    [0xd6, 0x60, 0x4d],
+    [0xb2, 0x18, 0x2b],
+];
+
+impl Colormap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "viridis" => Some(Colormap::Viridis),
+            "inferno" => Some(Colormap::Inferno),
+            "diverging" => Some(Colormap::Diverging),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Colormap::Viridis => "viridis",
+            Colormap::Inferno => "inferno",
+            Colormap::Diverging => "diverging",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Colormap::Viridis => Colormap::Inferno,
+            Colormap::Inferno => Colormap::Diverging,
+            Colormap::Diverging => Colormap::Viridis,
+        }
+    }
+
+    /// Opaque color at `t`, which is clamped between 0 and 1
+    pub fn color(&self, t: f64) -> Color {
+        let stops = match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Inferno => &INFERNO,
+            Colormap::Diverging => &DIVERGING,
+        };
+        let position = t.clamp(0f64, 1f64) * (stops.len() - 1) as f64;
+        let index = (position as usize).min(stops.len() - 2);
+        let fraction = position - index as f64;
+        let (from, to) = (stops[index], stops[index + 1]);
+        let channel = |i: usize| (from[i] as f64 + (to[i] as f64 - from[i] as f64) * fraction).round() as u8;
+        [channel(0), channel(1), channel(2), 255]
+    }
+}
+
+/// Quantity the color of a particle depends on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorBy {
+    /// A color drawn at random for each particle
+    Random,
+    Speed,
+    Mass,
+    KineticEnergy,
+    DistanceToCenterOfMass,
+}
+
+impl ColorBy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "random" => Some(ColorBy::Random),
+            "speed" => Some(ColorBy::Speed),
+            "mass" => Some(ColorBy::Mass),
+            "kinetic-energy" => Some(ColorBy::KineticEnergy),
+            "distance" => Some(ColorBy::DistanceToCenterOfMass),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorBy::Random => "random",
+            ColorBy::Speed => "speed",
+            ColorBy::Mass => "mass",
+            ColorBy::KineticEnergy => "kinetic-energy",
+            ColorBy::DistanceToCenterOfMass => "distance",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ColorBy::Random => ColorBy::Speed,
+            ColorBy::Speed => ColorBy::Mass,
+            ColorBy::Mass => ColorBy::KineticEnergy,
+            ColorBy::KineticEnergy => ColorBy::DistanceToCenterOfMass,
+            ColorBy::DistanceToCenterOfMass => ColorBy::Random,
+        }
+    }
+
+    // Mass and kinetic energy span orders of magnitude once particles merge, so they are compared on a logarithmic
+    // scale for the colormap not to be used by a handful of particles only
+    fn value(&self, particle: &Particle, center_of_mass: &Coordinates) -> f64 {
+        let speed_squared = distance_squared(particle.speed, Coordinates::default());
+        match self {
+            ColorBy::Random => 0f64,
+            ColorBy::Speed => speed_squared.sqrt(),
+            ColorBy::Mass => particle.mass.ln_1p(),
+            ColorBy::KineticEnergy => (particle.mass * speed_squared / 2f64).ln_1p(),
+            ColorBy::DistanceToCenterOfMass => distance_squared(particle.position, *center_of_mass).sqrt(),
+        }
+    }
+}
+
+fn center_of_mass(population: &Population) -> Coordinates {
+    let mut center_of_mass = Coordinates::default();
+    let mut mass = 0f64;
+    for particle in population.iter().filter(|particle| particle.mass != 0f64) {
+        mass += particle.mass;
+        for i in 0..DIMENSIONS {
+            center_of_mass[i] += particle.mass * particle.position[i];
+        }
+    }
+    if mass != 0f64 {
+        for i in 0..DIMENSIONS {
+            center_of_mass[i] /= mass;
+        }
+    }
+    center_of_mass
+}
+
+/// Color of each particle of `population`, `random_colors` holding the ones used by `ColorBy::Random`. The range of
+/// the quantity over the particles still in the simulation is mapped to the whole colormap.
+pub fn particles_colors(
+    population: &Population,
+    color_by: ColorBy,
+    colormap: Colormap,
+    random_colors: &[Color],
+) -> Vec<Color> {
+    if color_by == ColorBy::Random {
+        return random_colors.to_vec();
+    }
+    let center_of_mass = center_of_mass(population);
+    let values: Vec<f64> = population
+        .iter()
+        .map(|particle| color_by.value(particle, &center_of_mass))
+        .collect();
+    let (minimum, maximum) = population
+        .iter()
+        .zip(values.iter())
+        .filter(|(particle, _)| particle.mass != 0f64)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(minimum, maximum), (_, &value)| {
+            (minimum.min(value), maximum.max(value))
+        });
+    values
+        .iter()
+        .map(|value| {
+            if maximum > minimum {
+                colormap.color((value - minimum) / (maximum - minimum))
+            } else {
+                colormap.color(0.5)
+            }
+        })
+        .collect()
+}
+
+/// Random opaque color
+pub fn random_color(rng: &mut impl Rng) -> Color {
+    let [red, green, blue]: [u8; 3] = rng.random();
+    [red, green, blue, 255]
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::coloring::{ColorBy, Colormap, particles_colors, random_color};
+    use crate::physics::test::coordinates;
+    use crate::physics::{Coordinates, Particle};
+    use crate::random::SplitMix64;
+
+    #[test]
+    fn test_coloring() {
+        assert_eq!(Colormap::Viridis.color(0f64), [0x44, 0x01, 0x54, 255]);
+        assert_eq!(Colormap::Inferno.color(2f64), [0xfc, 0xff, 0xa4, 255]);
+        assert_eq!(Colormap::Diverging.color(0.5), [0xf7, 0xf7, 0xf7, 255]);
+        assert_eq!(Colormap::Viridis.color(1f64 / 16f64), [0x46, 0x15, 0x66, 255]);
+        assert_eq!(random_color(&mut SplitMix64::new(0))[3], 255);
+
+        let particle = |mass, speed| Particle {
+            mass,
+            speed: coordinates([speed, 0f64]),
+            position: Coordinates::default(),
+        };
+        let population = vec![
+            particle(1f64, 1f64),
+            particle(0f64, 100f64),
+            particle(1f64, 3f64),
+            particle(1f64, 2f64),
+        ];
+        let colors = particles_colors(&population, ColorBy::Speed, Colormap::Inferno, &[]);
+        // The removed particle is left out of the range
+        assert_eq!(colors[0], Colormap::Inferno.color(0f64));
+        assert_eq!(colors[2], Colormap::Inferno.color(1f64));
+        assert_eq!(colors[3], Colormap::Inferno.color(0.5));
+
+        let colors = particles_colors(&population, ColorBy::Mass, Colormap::Viridis, &[]);
+        assert_eq!(colors[0], Colormap::Viridis.color(0.5));
+        let random_colors = [[1, 2, 3, 255]; 4];
+        assert_eq!(
+            particles_colors(&population, ColorBy::Random, Colormap::Viridis, &random_colors),
+            random_colors
+        );
+    }
+}
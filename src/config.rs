@@ -0,0 +1,488 @@
+// Responsible for reading the settings of a run, first from a key=value file such as the *.env files at the root of
+// the repository, then from command line flags overriding them:
+//
+// rengine --config framebuffer_demo.env --g 2 --pop-size=500
+//
+// Flags are the keys of the file in kebab case. Only DIMENSIONS is still chosen at build time, a file asking for
+// another number of dimensions than the build being rejected.
+
+use crate::camera::{Camera, Perspective};
+use crate::coloring::{ColorBy, Colormap};
+use crate::keymap::Keymap;
+use crate::physics::integrators;
+use crate::physics::{Coordinates, DIMENSIONS, Gravity, Simulation, Solver};
+use crate::random;
+use crate::scenario;
+use crate::terminal::TerminalCharacters;
+use crate::video::VideoFormat;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParticleShape {
+    Circle,
+    Square,
+}
+
+/// What is left of the previous frames under the current one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trails {
+    /// Cleared between frames
+    Off,
+    /// Dimmed by `Config::trail_decay` every frame
+    Fading,
+    /// Never cleared
+    Persistent,
+}
+
+impl Trails {
+    pub fn next(&self) -> Self {
+        match self {
+            Trails::Off => Trails::Fading,
+            Trails::Fading => Trails::Persistent,
+            Trails::Persistent => Trails::Off,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Orthographic,
+    /// Only available with at least 3 dimensions
+    Perspective,
+}
+
+/// Where particles are drawn when not recording
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Renderer {
+    Framebuffer,
+    Terminal(TerminalCharacters),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Name of a scenario known to `scenario::from_name`
+    pub scenario: String,
+    /// Speed at which the two galaxies of the `galaxies` scenario approach each other, by default the one at which
+    /// they would just escape each other if they were point masses
+    pub galaxies_relative_speed: Option<f64>,
+    pub pop_size: usize,
+    pub g: f64,
+    pub minimal_distance: f64,
+    /// Number of steps to simulate, frames drawn while paused not counting, 0 meaning until the user quits
+    pub iterations: u32,
+    /// Updates per second to slow down to, 0 meaning as fast as possible
+    pub desired_ups: u16,
+    pub particle_shape: ParticleShape,
+    pub color_by: ColorBy,
+    pub colormap: Colormap,
+    pub trails: Trails,
+    /// Share of its brightness a pixel keeps from one frame to the next with fading trails, between 0 and 1
+    pub trail_decay: f64,
+    pub projection: Projection,
+    /// Vertical field of view of the perspective projection, in degrees
+    pub field_of_view: f64,
+    /// Point the perspective camera orbits around, given as `LOOK_AT=x,y,z`
+    pub look_at: Coordinates,
+    /// Distance from the perspective camera to `look_at`, by default the one at which `look_at` is drawn at the same
+    /// scale as without perspective
+    pub camera_distance: Option<f64>,
+    /// `framebuffer`, or `braille` or `half-block` to draw in the terminal
+    pub renderer: Renderer,
+    /// Whether the framebuffer waits for the vertical blank before showing a frame
+    pub vsync: bool,
+    /// Loaded from the file given as `KEYMAP`, see `keymap`
+    pub keymap: Keymap,
+    pub default_particle_mass: f64,
+    /// `brute-force` or `barnes-hut`, the latter with an opening angle of `theta`
+    pub solver: Solver,
+    /// Opening angle given by THETA, kept for a SOLVER given after it
+    pub theta: f64,
+    /// Name of an integrator known to `integrators::from_name`
+    pub integrator: String,
+    pub dt: f64,
+    /// Steps between two diagnostics, 0 disabling them
+    pub diagnostics_interval: u64,
+    /// Snapshot to start from
+    pub load_snapshot: Option<PathBuf>,
+    /// Where the population is saved at the end of a run
+    pub save_snapshot: Option<PathBuf>,
+    /// Seed of every random draw of the run, a new one being drawn unless given
+    pub seed: u64,
+    /// Where to record a video instead of drawing to the framebuffer, `-` meaning stdout
+    pub record: Option<String>,
+    pub record_format: VideoFormat,
+    pub record_width: usize,
+    pub record_height: usize,
+    /// Only stored in Y4M headers, frames are written as fast as they are rendered
+    pub record_fps: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            scenario: "random-in-screen".to_string(),
+            galaxies_relative_speed: None,
+            pop_size: 100,
+            g: 1f64,
+            minimal_distance: 0f64,
+            iterations: 1000,
+            desired_ups: 0,
+            particle_shape: ParticleShape::Circle,
+            color_by: ColorBy::Random,
+            colormap: Colormap::Viridis,
+            trails: Trails::Off,
+            trail_decay: 0.9,
+            projection: Projection::Orthographic,
+            field_of_view: 60f64,
+            look_at: Coordinates::default(),
+            camera_distance: None,
+            renderer: Renderer::Framebuffer,
+            vsync: false,
+            keymap: Keymap::default(),
+            default_particle_mass: 10f64,
+            solver: Solver::BruteForce,
+            theta: 0.5,
+            integrator: "euler".to_string(),
+            dt: 1f64,
+            diagnostics_interval: 0,
+            load_snapshot: None,
+            save_snapshot: None,
+            seed: random::new_seed(),
+            record: None,
+            record_format: VideoFormat::Y4m,
+            record_width: 1280,
+            record_height: 720,
+            record_fps: 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    Unreadable {
+        path: PathBuf,
+        reason: String,
+    },
+    /// Line of a config file which is not of the form key=value
+    Malformed {
+        path: PathBuf,
+        line: usize,
+    },
+    UnknownKey(String),
+    MissingValue(String),
+    InvalidValue {
+        key: String,
+        value: String,
+        expected: String,
+    },
+    UnexpectedArgument(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Unreadable { path, reason } => write!(f, "Unable to read {}: {reason}", path.display()),
+            ConfigError::Malformed { path, line } => {
+                write!(f, "{}:{line}: expected a line of the form KEY=value", path.display())
+            }
+            ConfigError::UnknownKey(key) => write!(f, "Unknown key {key}"),
+            ConfigError::MissingValue(key) => write!(f, "Missing value for {key}"),
+            ConfigError::InvalidValue { key, value, expected } => {
+                write!(f, "Invalid value {value:?} for {key}, expected {expected}")
+            }
+            ConfigError::UnexpectedArgument(argument) => write!(f, "Unexpected argument {argument:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Defaults overridden by the file given with `--config`, then by the other flags whatever their position
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut flags = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                return Err(ConfigError::UnexpectedArgument(arg));
+            };
+            let (key, value) = match flag.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => match args.next() {
+                    Some(value) => (flag.to_string(), value),
+                    None => return Err(ConfigError::MissingValue(arg)),
+                },
+            };
+            flags.push((key.replace('-', "_").to_uppercase(), value));
+        }
+
+        let mut config = Config::default();
+        for (_, path) in flags.iter().filter(|(key, _)| key == "CONFIG") {
+            config.load_file(Path::new(path))?;
+        }
+        for (key, value) in flags.iter().filter(|(key, _)| key != "CONFIG") {
+            config.set(key, value)?;
+        }
+        Ok(config)
+    }
+
+    pub fn load_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let content = fs::read_to_string(path).map_err(|err| ConfigError::Unreadable {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+        self.load_str(&content, path)
+    }
+
+    // Blank lines and lines starting with # are ignored
+    fn load_str(&mut self, content: &str, path: &Path) -> Result<(), ConfigError> {
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| ConfigError::Malformed {
+                path: path.to_path_buf(),
+                line: line_index + 1,
+            })?;
+            self.set(key.trim(), value.trim())?;
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = |expected: &str| ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+            expected: expected.to_string(),
+        };
+        match key {
+            "DIMENSIONS" => {
+                if parse::<usize>(key, value)? != DIMENSIONS {
+                    return Err(invalid(&format!(
+                        "{DIMENSIONS}, the number of dimensions of this build"
+                    )));
+                }
+            }
+            "SCENARIO" => match scenario::from_name(value) {
+                Some(_) => self.scenario = value.to_string(),
+                None => return Err(invalid(&scenario::NAMES.join(", "))),
+            },
+            "GALAXIES_RELATIVE_SPEED" => match parse::<f64>(key, value)? {
+                relative_speed if relative_speed > 0f64 => self.galaxies_relative_speed = Some(relative_speed),
+                _ => return Err(invalid("a positive number")),
+            },
+            "POP_SIZE" => self.pop_size = parse(key, value)?,
+            "G" => self.g = parse(key, value)?,
+            "MINIMAL_DISTANCE" => self.minimal_distance = parse(key, value)?,
+            "ITERATIONS" => self.iterations = parse(key, value)?,
+            "DESIRED_UPS" => self.desired_ups = parse(key, value)?,
+            "PARTICLE_SHAPE" => {
+                self.particle_shape = match value {
+                    "circle" => ParticleShape::Circle,
+                    "square" => ParticleShape::Square,
+                    _ => return Err(invalid("circle or square")),
+                }
+            }
+            "COLOR_BY" => match ColorBy::from_name(value) {
+                Some(color_by) => self.color_by = color_by,
+                None => return Err(invalid("random, speed, mass, kinetic-energy or distance")),
+            },
+            "COLORMAP" => match Colormap::from_name(value) {
+                Some(colormap) => self.colormap = colormap,
+                None => return Err(invalid("viridis, inferno or diverging")),
+            },
+            "TRAILS" => {
+                self.trails = match value {
+                    "off" => Trails::Off,
+                    "fading" => Trails::Fading,
+                    "persistent" => Trails::Persistent,
+                    _ => return Err(invalid("off, fading or persistent")),
+                }
+            }
+            "TRAIL_DECAY" => match parse::<f64>(key, value)? {
+                trail_decay if (0f64..1f64).contains(&trail_decay) => self.trail_decay = trail_decay,
+                _ => return Err(invalid("a number between 0 included and 1 excluded")),
+            },
+            "PROJECTION" => {
+                self.projection = match value {
+                    "orthographic" => Projection::Orthographic,
+                    "perspective" if DIMENSIONS >= 3 => Projection::Perspective,
+                    "perspective" => return Err(invalid("orthographic, perspective needing at least 3 dimensions")),
+                    _ => return Err(invalid("orthographic or perspective")),
+                }
+            }
+            "FIELD_OF_VIEW" => match parse::<f64>(key, value)? {
+                field_of_view if field_of_view > 0f64 && field_of_view < 180f64 => self.field_of_view = field_of_view,
+                _ => return Err(invalid("an angle in degrees between 0 and 180")),
+            },
+            "LOOK_AT" => {
+                let coordinates: Vec<f64> = value
+                    .split(',')
+                    .map(|x| parse(key, x.trim()))
+                    .collect::<Result<_, _>>()?;
+                self.look_at = Coordinates::new(
+                    coordinates
+                        .try_into()
+                        .map_err(|_| invalid(&format!("{DIMENSIONS} comma separated numbers")))?,
+                );
+            }
+            "CAMERA_DISTANCE" => match parse::<f64>(key, value)? {
+                camera_distance if camera_distance > 0f64 => self.camera_distance = Some(camera_distance),
+                _ => return Err(invalid("a positive number")),
+            },
+            "RENDERER" => {
+                self.renderer = match value {
+                    "framebuffer" => Renderer::Framebuffer,
+                    "braille" => Renderer::Terminal(TerminalCharacters::Braille),
+                    "half-block" => Renderer::Terminal(TerminalCharacters::HalfBlock),
+                    _ => return Err(invalid("framebuffer, braille or half-block")),
+                }
+            }
+            "VSYNC" => self.vsync = parse(key, value)?,
+            "KEYMAP" => self.keymap = Keymap::load_file(Path::new(value))?,
+            "DEFAULT_PARTICLE_MASS" => self.default_particle_mass = parse(key, value)?,
+            "SOLVER" => {
+                self.solver = match value {
+                    "brute-force" => Solver::BruteForce,
+                    "barnes-hut" => Solver::BarnesHut { theta: self.theta },
+                    _ => return Err(invalid("brute-force or barnes-hut")),
+                }
+            }
+            "THETA" => {
+                self.theta = parse(key, value)?;
+                if let Solver::BarnesHut { theta } = &mut self.solver {
+                    *theta = self.theta;
+                }
+            }
+            "INTEGRATOR" => match integrators::from_name(value) {
+                Some(_) => self.integrator = value.to_string(),
+                None => return Err(invalid("euler, leapfrog, velocity-verlet or rk4")),
+            },
+            "DT" => self.dt = parse(key, value)?,
+            "DIAGNOSTICS_INTERVAL" => self.diagnostics_interval = parse(key, value)?,
+            "LOAD_SNAPSHOT" => self.load_snapshot = Some(PathBuf::from(value)),
+            "SAVE_SNAPSHOT" => self.save_snapshot = Some(PathBuf::from(value)),
+            "SEED" => self.seed = parse(key, value)?,
+            "RECORD" => self.record = Some(value.to_string()),
+            "RECORD_FORMAT" => {
+                self.record_format = match value {
+                    "ppm" => VideoFormat::Ppm,
+                    "y4m" => VideoFormat::Y4m,
+                    _ => return Err(invalid("ppm or y4m")),
+                }
+            }
+            "RECORD_WIDTH" => self.record_width = parse(key, value)?,
+            "RECORD_HEIGHT" => self.record_height = parse(key, value)?,
+            "RECORD_FPS" => self.record_fps = parse(key, value)?,
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn gravity(&self) -> Gravity {
+        Gravity {
+            g: self.g,
+            minimal_distance: self.minimal_distance,
+            solver: self.solver,
+        }
+    }
+
+    /// Initial camera for a screen `height` pixels high
+    pub fn camera(&self, height: usize) -> Camera {
+        Camera {
+            perspective: (self.projection == Projection::Perspective).then(|| {
+                let perspective = Perspective::new(self.field_of_view.to_radians(), height);
+                Perspective {
+                    look_at: self.look_at,
+                    distance: self.camera_distance.unwrap_or(perspective.distance),
+                    ..perspective
+                }
+            }),
+            ..Camera::default()
+        }
+    }
+
+    pub fn simulation(&self) -> Simulation {
+        Simulation {
+            gravity: self.gravity(),
+            integrator: integrators::from_name(&self.integrator).expect("Integrator is checked when set"),
+            dt: self.dt,
+        }
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+        expected: std::any::type_name::<T>().to_string(),
+    })
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::config::{Config, ConfigError, Trails};
+    use crate::physics::{Coordinates, DIMENSIONS, Solver};
+    use std::path::Path;
+
+    #[test]
+    fn test_config() {
+        let mut config = Config::default();
+        config
+            .load_str(
+                // Written for the 2D builds the end to end test runs on
+                &include_str!("../framebuffer_test.env").replace("DIMENSIONS=2", &format!("DIMENSIONS={DIMENSIONS}")),
+                Path::new("framebuffer_test.env"),
+            )
+            .unwrap();
+        assert_eq!(config.pop_size, 3);
+        assert_eq!(config.minimal_distance, 3f64);
+
+        let args = ["--pop-size", "50", "--integrator=rk4"].map(String::from);
+        let config = Config::from_args(args).unwrap();
+        assert_eq!(config.pop_size, 50);
+        assert_eq!(config.integrator, "rk4");
+
+        let args = ["--solver", "barnes-hut", "--theta", "0.7"].map(String::from);
+        assert_eq!(
+            Config::from_args(args).unwrap().solver,
+            Solver::BarnesHut { theta: 0.7 }
+        );
+        let args = ["--solver", "octree"].map(String::from);
+        let error = Config::from_args(args).unwrap_err();
+        assert!(matches!(error, ConfigError::InvalidValue { ref key, .. } if key == "SOLVER"));
+
+        let args = ["--g", "heavy"].map(String::from);
+        let error = Config::from_args(args).unwrap_err();
+        assert!(matches!(error, ConfigError::InvalidValue { ref key, .. } if key == "G"));
+        assert!(error.to_string().contains("G"));
+
+        let args = ["--trails=fading", "--trail-decay", "0.5"].map(String::from);
+        let config = Config::from_args(args).unwrap();
+        assert_eq!((config.trails, config.trail_decay), (Trails::Fading, 0.5));
+        let args = ["--trail-decay", "1"].map(String::from);
+        assert!(Config::from_args(args).is_err());
+
+        let look_at: Vec<String> = (0..DIMENSIONS).map(|i| format!("{}", 1f64 - 3.5 * i as f64)).collect();
+        let args = ["--look-at", &look_at.join(", "), "--camera-distance", "300"].map(String::from);
+        let config = Config::from_args(args).unwrap();
+        assert_eq!(
+            config.look_at,
+            Coordinates::new(std::array::from_fn(|i| 1f64 - 3.5 * i as f64))
+        );
+        assert_eq!(config.camera_distance, Some(300f64));
+        let args = ["--look-at", &["1"; DIMENSIONS + 1].join(",")].map(String::from);
+        assert!(Config::from_args(args).is_err());
+        let args = ["--camera-distance", "0"].map(String::from);
+        assert!(Config::from_args(args).is_err());
+
+        let args = ["--galaxies-relative-speed", "2.5"].map(String::from);
+        assert_eq!(Config::from_args(args).unwrap().galaxies_relative_speed, Some(2.5));
+        let args = ["--galaxies-relative-speed", "-1"].map(String::from);
+        assert!(Config::from_args(args).is_err());
+    }
+}
@@ -1,158 +1,479 @@
-use crate::physics::{DIMENSIONS, POP_SIZE, Population, apply_force};
+mod console;
+mod dirty;
+mod ioctl;
+
+use crate::camera::{Camera, ROTATION_STEP};
+use crate::coloring;
+use crate::coloring::ColorBy;
+use crate::config::{Config, ParticleShape, Trails};
+use crate::font;
+use crate::framebuffer::console::Console;
+use crate::framebuffer::dirty::DirtyRows;
+use crate::framebuffer::ioctl::{Bitfield, VariableScreenInfo};
+use crate::input::{INPUT_DEVICES_DIRECTORY, InputDevices};
+use crate::keymap::Action;
+use crate::physics::diagnostics::DiagnosticsLog;
+use crate::physics::{DIMENSIONS, Particle, Population, Simulation};
+use crate::render;
+use crate::render::{Color, RenderTarget, SavedRectangle};
+use crate::snapshot;
 use memmap2::{MmapMut, MmapOptions};
-use proc_macros::{get_desired_ups_from_env_var, get_iterations_from_env_var, get_particle_shape_from_env_var};
-use rand::random;
+use rand::Rng;
 use std::fs::{File, OpenOptions};
-use std::io::{ErrorKind, Read};
-use std::mem::transmute;
-use std::os::unix::ffi::OsStrExt;
-use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
-use std::{array, fs};
 
-const BYTES_PER_PIXEL: usize = 4;
-const SCREEN_WIDTH: usize = 2560;
-const SCREEN_HEIGHT: usize = 1440;
+/// How colors are laid out in the memory of the framebuffer device
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PixelFormat {
+    bytes_per_pixel: usize,
+    red: Bitfield,
+    green: Bitfield,
+    blue: Bitfield,
+    transp: Bitfield,
+}
 
-const FRAMEBUFFER_LENGTH: usize = SCREEN_WIDTH * SCREEN_HEIGHT * BYTES_PER_PIXEL;
+impl PixelFormat {
+    /// Bytes of `color` in the device format, only the first `bytes_per_pixel` being meaningful
+    fn encode(&self, color: &Color) -> [u8; 4] {
+        let [red, green, blue, alpha] = *color;
+        let value = Self::encode_channel(red, &self.red)
+            | Self::encode_channel(green, &self.green)
+            | Self::encode_channel(blue, &self.blue)
+            | Self::encode_channel(alpha, &self.transp);
+        value.to_le_bytes()
+    }
 
-const DESIRED_UPS: u16 = get_desired_ups_from_env_var!();
-const DESIRED_UPDATE_DURATION: Duration = if DESIRED_UPS == 0 {
-    Duration::ZERO
-} else {
-    Duration::from_micros(1000000 / DESIRED_UPS as u64)
-};
+    /// Scale down every channel of the device encoded `pixel`, see `render::fade_factor`
+    fn fade(&self, pixel: &mut [u8], factor: u32) {
+        let mut bytes = [0u8; 4];
+        bytes[..pixel.len()].copy_from_slice(pixel);
+        let value = u32::from_le_bytes(bytes);
+        let faded = [&self.red, &self.green, &self.blue]
+            .into_iter()
+            .map(|bitfield| ((((value & Self::mask(bitfield)) >> bitfield.offset) * factor) >> 8) << bitfield.offset)
+            .fold(value & Self::mask(&self.transp), |faded, channel| faded | channel);
+        pixel.copy_from_slice(&faded.to_le_bytes()[..pixel.len()]);
+    }
 
-const ITERATIONS: u32 = get_iterations_from_env_var!();
+    // Bits of `value` holding the channel
+    fn mask(bitfield: &Bitfield) -> u32 {
+        (1u32 << bitfield.length).wrapping_sub(1) << bitfield.offset
+    }
 
-const PARTICLE_SHAPE: &str = get_particle_shape_from_env_var!();
+    fn encode_channel(value: u8, bitfield: &Bitfield) -> u32 {
+        let value = value as u32;
+        let scaled = if bitfield.length >= 8 {
+            value << (bitfield.length - 8)
+        } else {
+            value >> (8 - bitfield.length)
+        };
+        scaled << bitfield.offset
+    }
+}
 
-struct Framebuffer {
+// Share of the screen above which a frame is presented by copying the whole back buffer, which is then faster than
+// copying row spans one by one
+const FULL_COPY_AREA_RATIO: f64 = 0.5;
+
+// Virtual screen twice as high as the visible one, whose halves are shown in turn so that frames are never seen while
+// being copied
+struct PageFlipping {
+    screen_info: VariableScreenInfo,
+    // To give the console its virtual screen back
+    original_screen_info: VariableScreenInfo,
+    // Page being written to, 0 for the top half and 1 for the bottom one
+    hidden_page: usize,
+}
+
+/// Linux framebuffer device, drawn into through a back buffer already in the device pixel format. Only what changed
+/// since the last frame is copied to the device, into a hidden page which is then shown when the driver allows it.
+pub struct Framebuffer {
+    file: File,
     mmap: Box<MmapMut>,
     buffer: Vec<u8>,
+    width: usize,
+    height: usize,
+    line_length: usize,
+    // Where the visible screen starts in the device memory, when not flipping pages
+    visible_offset: usize,
+    page_flipping: Option<PageFlipping>,
+    // Whether to wait for the vertical blank before showing a frame, turned off if the driver cannot
+    vsync: bool,
+    pixel_format: PixelFormat,
+    console: Option<Console>,
+    // Drawn since the last clear, hence what the next clear has to wipe
+    drawn: DirtyRows,
+    // Wiped since the last present, hence to be copied along with what is drawn
+    wiped: DirtyRows,
+    // Presented the frame before, which the hidden page still lacks when flipping pages
+    previously_presented: DirtyRows,
+    // Pages of the device memory out of sync with the back buffer as a whole, as before the first frames
+    full_copies_needed: usize,
 }
 
 impl Framebuffer {
-    pub fn new() -> Self {
+    pub fn new(vsync: bool) -> Self {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
             .open("/dev/fb0")
             .expect("Unable to open framebuffer device");
+        let page_flipping = Self::enable_page_flipping(&file);
+        let variable_info = ioctl::get_variable_screen_info(&file).expect("Unable to get framebuffer variable info");
+        let fixed_info = ioctl::get_fixed_screen_info(&file).expect("Unable to get framebuffer fixed info");
+
+        let pixel_format = PixelFormat {
+            bytes_per_pixel: variable_info.bits_per_pixel.div_ceil(8) as usize,
+            red: variable_info.red,
+            green: variable_info.green,
+            blue: variable_info.blue,
+            transp: variable_info.transp,
+        };
+        if pixel_format.bytes_per_pixel == 0 || pixel_format.bytes_per_pixel > 4 {
+            panic!("Unsupported framebuffer depth of {} bits", variable_info.bits_per_pixel)
+        }
+        let width = variable_info.xres as usize;
+        let height = variable_info.yres as usize;
+        let line_length = fixed_info.line_length as usize;
+        let visible_offset = variable_info.yoffset as usize * line_length
+            + variable_info.xoffset as usize * pixel_format.bytes_per_pixel;
+
         let mmap = unsafe {
             MmapOptions::new()
-                .len(FRAMEBUFFER_LENGTH)
+                .len(fixed_info.smem_len as usize)
                 .map_mut(&file)
                 .expect("Unable to mmap framebuffer")
         };
+        let pages = if page_flipping.is_some() { 2 } else { 1 };
+        if page_flipping.is_none() && visible_offset + line_length * height > mmap.len()
+            || line_length * height * pages > mmap.len()
+        {
+            panic!("Framebuffer memory is too small for its reported geometry")
+        }
         Framebuffer {
+            file,
             mmap: Box::new(mmap),
-            buffer: vec![0; FRAMEBUFFER_LENGTH],
+            buffer: vec![0; line_length * height],
+            width,
+            height,
+            line_length,
+            visible_offset,
+            page_flipping,
+            vsync,
+            pixel_format,
+            console: Console::enter_graphics_mode(),
+            drawn: DirtyRows::new(height),
+            wiped: DirtyRows::new(height),
+            previously_presented: DirtyRows::new(height),
+            full_copies_needed: pages,
+        }
+    }
+
+    // Make the virtual screen twice as high as the visible one if needed. None if the driver does not allow it or
+    // cannot pan between both halves, the virtual screen then being left as it was.
+    fn enable_page_flipping(file: &File) -> Option<PageFlipping> {
+        let original_screen_info = ioctl::get_variable_screen_info(file).ok()?;
+        let mut screen_info = original_screen_info;
+        screen_info.yres_virtual = screen_info.yres_virtual.max(screen_info.yres * 2);
+        screen_info.xoffset = 0;
+        screen_info.yoffset = 0;
+        let screen_info = ioctl::put_variable_screen_info(file, &screen_info)
+            .and_then(|_| ioctl::get_variable_screen_info(file))
+            .ok()
+            .filter(|screen_info| screen_info.yres_virtual >= screen_info.yres * 2);
+        let fixed_info = ioctl::get_fixed_screen_info(file).ok()?;
+        let screen_info = screen_info.filter(|screen_info| {
+            fixed_info.ypanstep != 0
+                && screen_info.yres.is_multiple_of(fixed_info.ypanstep as u32)
+                && fixed_info.line_length as usize * screen_info.yres as usize * 2 <= fixed_info.smem_len as usize
+                && ioctl::pan_display(file, screen_info).is_ok()
+        });
+        match screen_info {
+            Some(screen_info) => Some(PageFlipping {
+                screen_info,
+                original_screen_info,
+                hidden_page: 1,
+            }),
+            None => {
+                let _ = ioctl::put_variable_screen_info(file, &original_screen_info);
+                None
+            }
         }
     }
+}
 
-    pub fn clear(&mut self) {
-        self.buffer.fill(0);
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        self.release();
     }
+}
 
-    pub fn draw_pixel(&mut self, x: isize, y: isize, color: &[u8; BYTES_PER_PIXEL]) {
-        if x < 0 || x >= SCREEN_WIDTH as isize || y < 0 || y >= SCREEN_HEIGHT as isize {
-            return;
+// Copy the `rows` spans of `buffer` to `memory`, or all of it
+fn copy_rows(memory: &mut [u8], buffer: &[u8], rows: Option<&DirtyRows>, line_length: usize, bytes_per_pixel: usize) {
+    match rows {
+        None => memory[..buffer.len()].copy_from_slice(buffer),
+        Some(rows) => {
+            for (y, start, end) in rows.spans() {
+                let span = y * line_length + start * bytes_per_pixel..y * line_length + end * bytes_per_pixel;
+                memory[span.clone()].copy_from_slice(&buffer[span]);
+            }
         }
-        let anchor_pixel_index = (y * (SCREEN_WIDTH as isize) + x) * (BYTES_PER_PIXEL as isize);
-        if anchor_pixel_index >= 0 && anchor_pixel_index + (BYTES_PER_PIXEL as isize) < (FRAMEBUFFER_LENGTH as isize) {
-            let anchor_pixel_index = anchor_pixel_index as usize;
-            let pixel_slice = &mut self.buffer[anchor_pixel_index..anchor_pixel_index + BYTES_PER_PIXEL];
-            pixel_slice.copy_from_slice(color);
+    }
+}
+
+impl RenderTarget for Framebuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn clear(&mut self) {
+        let bytes_per_pixel = self.pixel_format.bytes_per_pixel;
+        for (y, start, end) in self.drawn.spans() {
+            let line = y * self.line_length;
+            self.buffer[line + start * bytes_per_pixel..line + end * bytes_per_pixel].fill(0);
         }
+        self.wiped.merge(&self.drawn);
+        self.drawn.reset();
     }
 
-    pub fn draw_square(&mut self, x: isize, y: isize, width: usize, height: usize, color: &[u8; BYTES_PER_PIXEL]) {
-        for i in x..(x + (width as isize)) {
-            for j in y..(y + (height as isize)) {
-                self.draw_pixel(i, j, color);
+    // Drawn pixels stay lit, so they remain to be wiped by the next clear
+    fn fade(&mut self, decay: f64) {
+        let factor = render::fade_factor(decay);
+        let bytes_per_pixel = self.pixel_format.bytes_per_pixel;
+        for (y, start, end) in self.drawn.spans() {
+            let line = y * self.line_length;
+            for pixel in self.buffer[line + start * bytes_per_pixel..line + end * bytes_per_pixel]
+                .chunks_exact_mut(bytes_per_pixel)
+            {
+                self.pixel_format.fade(pixel, factor);
             }
         }
     }
 
-    pub fn draw_circle(&mut self, x: isize, y: isize, radius: usize, color: &[u8; BYTES_PER_PIXEL]) {
-        let rsqr = (radius * radius) as isize;
-        for dx in -(radius as isize)..=(radius as isize) {
-            for dy in -(radius as isize)..=(radius as isize) {
-                if dx * dx + dy * dy <= rsqr {
-                    let px = x + dx;
-                    let py = y + dy;
-                    self.draw_pixel(px, py, color);
-                }
+    fn draw_pixel(&mut self, x: isize, y: isize, color: &Color) {
+        if x < 0 || x >= self.width as isize || y < 0 || y >= self.height as isize {
+            return;
+        }
+        let bytes_per_pixel = self.pixel_format.bytes_per_pixel;
+        let anchor_pixel_index = y as usize * self.line_length + x as usize * bytes_per_pixel;
+        self.buffer[anchor_pixel_index..anchor_pixel_index + bytes_per_pixel]
+            .copy_from_slice(&self.pixel_format.encode(color)[..bytes_per_pixel]);
+        self.drawn.add(x as usize, y as usize);
+    }
+
+    fn draws_cursor(&self) -> bool {
+        true
+    }
+
+    fn save(&self, x: isize, y: isize, width: usize, height: usize) -> Option<SavedRectangle> {
+        let layout = (self.width, self.line_length, self.pixel_format.bytes_per_pixel);
+        Some(SavedRectangle::copy(&self.buffer, layout, x, y, width, height))
+    }
+
+    // Restored pixels are presented with the next frame
+    fn restore(&mut self, saved: &SavedRectangle) {
+        saved.paste(&mut self.buffer, self.line_length, self.pixel_format.bytes_per_pixel);
+        if saved.width > 0 {
+            for y in saved.y..saved.y + saved.height {
+                self.drawn.add(saved.x, y);
+                self.drawn.add(saved.x + saved.width - 1, y);
             }
         }
     }
 
-    pub fn draw(&mut self) {
-        self.mmap.copy_from_slice(self.buffer.as_slice());
+    fn release(&mut self) {
+        if let Some(page_flipping) = self.page_flipping.take() {
+            let _ = ioctl::put_variable_screen_info(&self.file, &page_flipping.original_screen_info);
+        }
+        self.console = None;
+    }
+
+    fn draw(&mut self) {
+        self.wiped.merge(&self.drawn);
+        // The hidden page was last written two frames ago, so it also lacks what changed in the previous frame
+        let (offset, presented) = match &self.page_flipping {
+            Some(page_flipping) => {
+                self.previously_presented.merge(&self.wiped);
+                (
+                    page_flipping.hidden_page * self.height * self.line_length,
+                    &self.previously_presented,
+                )
+            }
+            None => (self.visible_offset, &self.wiped),
+        };
+        let full_copy = self.full_copies_needed > 0
+            || presented.area() as f64 > (self.width * self.height) as f64 * FULL_COPY_AREA_RATIO;
+        copy_rows(
+            &mut self.mmap[offset..],
+            &self.buffer,
+            (!full_copy).then_some(presented),
+            self.line_length,
+            self.pixel_format.bytes_per_pixel,
+        );
+        self.full_copies_needed = self.full_copies_needed.saturating_sub(1);
+
+        if self.vsync && ioctl::wait_for_vsync(&self.file).is_err() {
+            self.vsync = false;
+        }
+        if let Some(page_flipping) = &mut self.page_flipping {
+            page_flipping.screen_info.yoffset = (page_flipping.hidden_page * self.height) as u32;
+            if ioctl::pan_display(&self.file, &page_flipping.screen_info).is_ok() {
+                page_flipping.hidden_page = 1 - page_flipping.hidden_page;
+            }
+            // Only what changed in this frame is left for the next one
+            std::mem::swap(&mut self.wiped, &mut self.previously_presented);
+        }
+        self.wiped.reset();
     }
 }
 
-fn open_input_event_devices(device_type: &str) -> Vec<File> {
-    fs::read_dir("/dev/input/by-id")
-        .into_iter()
-        .flat_map(|entries| entries.flatten())
-        .filter(|entry| {
-            entry
-                .file_name()
-                .as_bytes()
-                .ends_with([b"event-", device_type.as_bytes()].concat().as_slice())
-        })
-        .filter_map(|entry| {
-            OpenOptions::new()
-                .read(true)
-                .custom_flags(0x800)
-                .open(entry.path())
-                .ok()
-        })
-        .collect()
+// Fastest finite UPS, going beyond it removes the limit
+const MAX_DESIRED_UPS: u16 = 1000;
+
+fn slower_ups(desired_ups: u16) -> u16 {
+    match desired_ups {
+        0 => MAX_DESIRED_UPS,
+        desired_ups => (desired_ups / 2).max(1),
+    }
 }
 
-#[repr(C)]
-#[derive(Debug)]
-struct InputEvent {
-    time: [u64; 2],
-    type_: u16,
-    code: u16,
-    value: i32,
+fn faster_ups(desired_ups: u16) -> u16 {
+    match desired_ups {
+        0 => 0,
+        desired_ups if desired_ups > MAX_DESIRED_UPS / 2 => 0,
+        desired_ups => desired_ups * 2,
+    }
 }
 
-fn read_input_events(files: &mut [File]) -> Vec<InputEvent> {
-    let mut events = vec![];
-    for file in files {
-        let mut buffer = [0u8; 24];
-        match file.read(&mut buffer) {
-            Ok(24) => events.extend(buffer.chunks_exact(24).map(|chunk| {
-                let chunk: [u8; 24] = chunk.try_into().unwrap();
-                unsafe { transmute(chunk) }
-            })),
-            Ok(_) => {}
-            Err(err) => {
-                if err.kind() == ErrorKind::WouldBlock {
-                    break;
-                }
-            }
-        }
+// Closest to 1 the trail decay can get, trails then lasting a few hundred frames
+const MAX_TRAIL_DECAY: f64 = 0.995;
+
+// Each step divides or multiplies by 1.5 the share of brightness lost every frame
+fn longer_trails(trail_decay: f64) -> f64 {
+    (1f64 - (1f64 - trail_decay) / 1.5).min(MAX_TRAIL_DECAY)
+}
+
+fn shorter_trails(trail_decay: f64) -> f64 {
+    (1f64 - (1f64 - trail_decay) * 1.5).max(0f64)
+}
+
+// Radians turned per unit of mouse motion while dragging
+const DRAG_ROTATION: f64 = 0.005;
+
+// Clicks farther than this from any particle, in pixels, select nothing
+const PICKING_DISTANCE: isize = 20;
+
+// Index of the drawn particle nearest to `cursor`, if close enough
+fn pick_particle(
+    population: &Population,
+    camera: &Camera,
+    width: usize,
+    height: usize,
+    cursor: (isize, isize),
+) -> Option<usize> {
+    population
+        .iter()
+        .enumerate()
+        .filter(|(_, particle)| particle.mass != 0f64)
+        .filter_map(|(particle_index, particle)| {
+            let projected = camera.project(&particle.position, width, height)?;
+            let radius = (particle.mass.sqrt() * projected.scale) as isize;
+            let distance_squared = (projected.x - cursor.0).pow(2) + (projected.y - cursor.1).pow(2);
+            Some((particle_index, distance_squared, (radius.max(PICKING_DISTANCE)).pow(2)))
+        })
+        .filter(|(_, distance_squared, maximal_distance_squared)| distance_squared <= maximal_distance_squared)
+        .min_by_key(|(_, distance_squared, _)| *distance_squared)
+        .map(|(particle_index, _, _)| particle_index)
+}
+
+const PANEL_COLOR: Color = [255, 255, 255, 255];
+const PANEL_BACKGROUND: Color = [0, 0, 0, 255];
+const PANEL_MARGIN: usize = 8;
+
+// Describe the selected particle in the bottom left corner, returning what the panel covers
+fn draw_particle_panel(
+    framebuffer: &mut impl RenderTarget,
+    particle_index: usize,
+    particle: &Particle,
+) -> Option<SavedRectangle> {
+    let mut text = format!("Particle {particle_index}\nMass: {}", particle.mass);
+    for i in 0..DIMENSIONS {
+        text += &format!("\nSpeed {i}: {:.6}", particle.speed[i]);
     }
-    events
+    for i in 0..DIMENSIONS {
+        text += &format!("\nPosition {i}: {:.6}", particle.position[i]);
+    }
+    let (width, height) = panel_size(&text);
+    let y = framebuffer.height() as isize - (height + PANEL_MARGIN) as isize;
+    let covered = framebuffer.save(PANEL_MARGIN as isize, y, width, height);
+    draw_panel(framebuffer, PANEL_MARGIN as isize, y, &text);
+    covered
+}
+
+// Size of the panel showing `text`, background included
+fn panel_size(text: &str) -> (usize, usize) {
+    let (width, height) = font::text_size(text, 1);
+    (width + PANEL_MARGIN, height + PANEL_MARGIN)
 }
 
-pub fn run(population: &mut Population) {
-    let mut keyboards = open_input_event_devices("kbd");
-    let mut mouses = open_input_event_devices("mouse");
-    let mut framebuffer = Framebuffer::new();
+// Draw `text` over an opaque background, with its top left corner at `x`, `y`
+fn draw_panel(framebuffer: &mut impl RenderTarget, x: isize, y: isize, text: &str) {
+    let (width, height) = panel_size(text);
+    framebuffer.draw_square(x, y, width, height, &PANEL_BACKGROUND);
+    framebuffer.draw_text(
+        x + PANEL_MARGIN as isize / 2,
+        y + PANEL_MARGIN as isize / 2,
+        text,
+        1,
+        &PANEL_COLOR,
+    );
+}
 
-    let particles_colors: [[u8; BYTES_PER_PIXEL]; POP_SIZE] = array::from_fn(|_| random());
+// Cross centered on the mouse pointer, returning what it covers
+fn draw_cursor(framebuffer: &mut impl RenderTarget, cursor: (isize, isize)) -> Option<SavedRectangle> {
+    let covered = framebuffer.save(cursor.0 - 5, cursor.1 - 5, 11, 11);
+    framebuffer.draw_square(cursor.0 - 5, cursor.1, 11, 1, &PANEL_COLOR);
+    framebuffer.draw_square(cursor.0, cursor.1 - 5, 1, 11, &PANEL_COLOR);
+    covered
+}
 
-    let mut zoom: f64 = 1.0;
-    let mut shift: (isize, isize) = (0, 0);
-    let mut clear_between_frames = true;
+pub fn run(
+    framebuffer: &mut impl RenderTarget,
+    population: &mut Population,
+    simulation: &mut Simulation,
+    config: &Config,
+    first_step: u64,
+    mut camera: Camera,
+    rng: &mut impl Rng,
+) {
+    let mut desired_ups = config.desired_ups;
+
+    console::handle_quit_signals();
+    let mut keyboards = InputDevices::new(Path::new(INPUT_DEVICES_DIRECTORY), "kbd");
+    let mut mouses = InputDevices::new(Path::new(INPUT_DEVICES_DIRECTORY), "mouse");
+
+    // Used when coloring at random, so that each particle keeps its color
+    let mut random_colors: Vec<Color> = population.iter().map(|_| coloring::random_color(rng)).collect();
+    let mut color_by = config.color_by;
+    let mut colormap = config.colormap;
+
+    let mut cursor = (framebuffer.width() as isize / 2, framebuffer.height() as isize / 2);
+    let mut selected_particle: Option<usize> = None;
+    // Whether the right button is held, mouse motion then rotating the camera instead of moving the cursor
+    let mut dragging = false;
+    let mut show_hud = false;
+    // UPS measured over the last second, for the HUD
+    let mut live_ups = 0f64;
+    let mut live_ups_start = Instant::now();
+    let mut live_ups_frames = 0u32;
+    let mut trails = config.trails;
+    let mut trail_decay = config.trail_decay;
 
     framebuffer.clear();
 
@@ -161,120 +482,383 @@ pub fn run(population: &mut Population) {
     let mut total_drawing_time = Duration::ZERO;
     let mut total_clearing_screen_time = Duration::ZERO;
     let mut total_input_handling_time = Duration::ZERO;
+    let mut total_diagnostics_time = Duration::ZERO;
+
+    let mut diagnostics = DiagnosticsLog::new(config.diagnostics_interval, simulation.gravity);
+    diagnostics.record(first_step, population);
 
     let engine_start_instant = Instant::now();
 
     let mut quit = false;
-
-    let mut dim_0: usize = 0;
-    let mut dim_1: usize = 1;
+    let mut paused = false;
+    let mut single_step = false;
+    // Steps actually simulated, which is less than the number of frames when paused
+    let mut steps: u64 = 0;
 
     let mut i = 0;
     loop {
         i += 1;
-        if i == ITERATIONS || quit {
+        // Only simulated steps count, so that pausing does not shorten the run. As when frames were counted, the run
+        // stops after ITERATIONS - 1 steps, which the e2e-test build relies on.
+        if steps + 1 == config.iterations as u64 || quit || console::quit_requested() {
             break;
         }
         let update_start = Instant::now();
 
         let start = Instant::now();
-        for kb_event in read_input_events(&mut keyboards) {
-            if kb_event.type_ == 1 {
-                match kb_event.code {
-                    105 => shift.0 += 10, // LEFT
-                    106 => shift.0 -= 10, // RIGHT
-                    103 => shift.1 += 10, // UP
-                    108 => shift.1 -= 10, // DOWN
-                    20 => {
-                        // T
-                        if kb_event.value == 1 {
-                            clear_between_frames = !clear_between_frames
-                        }
-                    }
-                    16 => quit = true, // Q
-                    19 => {
-                        // R
-                        if kb_event.value == 1 {
-                            dim_0 = (dim_0 + 1) % DIMENSIONS;
-                            dim_1 = (dim_1 + 1) % DIMENSIONS;
-                        }
+        let (surface_key_events, surface_mouse_events): (Vec<_>, Vec<_>) = framebuffer
+            .input_events()
+            .into_iter()
+            .partition(|event| event.type_ == 1);
+        for kb_event in keyboards.read_events().into_iter().chain(surface_key_events) {
+            if kb_event.type_ != 1 {
+                continue;
+            }
+            match config.keymap.action(kb_event.code) {
+                Some(action) if action.is_triggered_by(kb_event.value) => match action {
+                    Action::PanLeft => camera.shift.0 += 10,
+                    Action::PanRight => camera.shift.0 -= 10,
+                    Action::PanUp => camera.shift.1 += 10,
+                    Action::PanDown => camera.shift.1 -= 10,
+                    Action::ZoomIn => camera.zoom *= 1.1,
+                    Action::ZoomOut => camera.zoom *= 0.9,
+                    Action::RotateDimensions => camera.cycle_axes(),
+                    Action::NextRotationPlane => camera.next_plane(),
+                    Action::RotateForward => camera.rotate(ROTATION_STEP),
+                    Action::RotateBackward => camera.rotate(-ROTATION_STEP),
+                    Action::ResetCamera => camera.reset(),
+                    Action::ToggleTrails => trails = trails.next(),
+                    Action::LongerTrails => trail_decay = longer_trails(trail_decay),
+                    Action::ShorterTrails => trail_decay = shorter_trails(trail_decay),
+                    Action::CycleColoring => color_by = color_by.next(),
+                    Action::CycleColormap => colormap = colormap.next(),
+                    Action::ToggleHud => show_hud = !show_hud,
+                    Action::TogglePause => paused = !paused,
+                    // Pausing if needed
+                    Action::Step => {
+                        paused = true;
+                        single_step = true;
                     }
-                    _ => {}
-                }
+                    Action::SlowerUps => desired_ups = slower_ups(desired_ups),
+                    Action::FasterUps => desired_ups = faster_ups(desired_ups),
+                    Action::Quit => quit = true,
+                },
+                _ => {}
             }
         }
 
-        for mouse_event in read_input_events(&mut mouses) {
-            if mouse_event.type_ == 2 && mouse_event.code == 8 {
-                match mouse_event.value {
-                    1 => zoom *= 1.1,
-                    -1 => zoom *= 0.9,
+        for mouse_event in mouses.read_events().into_iter().chain(surface_mouse_events) {
+            match (mouse_event.type_, mouse_event.code) {
+                // Relative X motion while dragging, Y motion being ignored
+                (2, 0) if dragging => camera.rotate(mouse_event.value as f64 * DRAG_ROTATION),
+                // Relative X and Y motion
+                (2, 0) => cursor.0 = (cursor.0 + mouse_event.value as isize).clamp(0, framebuffer.width() as isize - 1),
+                (2, 1) if !dragging => {
+                    cursor.1 = (cursor.1 + mouse_event.value as isize).clamp(0, framebuffer.height() as isize - 1)
+                }
+                // Wheel
+                (2, 8) => match mouse_event.value {
+                    1 => camera.zoom *= 1.1,
+                    -1 => camera.zoom *= 0.9,
                     _ => {}
+                },
+                // Right button
+                (1, 273) => dragging = mouse_event.value != 0,
+                // Left button press
+                (1, 272) if mouse_event.value == 1 => {
+                    selected_particle =
+                        pick_particle(population, &camera, framebuffer.width(), framebuffer.height(), cursor)
                 }
+                _ => {}
             }
         }
         total_input_handling_time += start.elapsed();
 
-        let start = Instant::now();
-        apply_force(population);
-        total_simulation_time += start.elapsed();
+        if !paused || single_step {
+            single_step = false;
+
+            let start = Instant::now();
+            let removed_particles = simulation.step(population);
+            for particle_index in removed_particles.into_iter().rev() {
+                random_colors.remove(particle_index);
+                selected_particle = match selected_particle {
+                    Some(selected) if selected == particle_index => None,
+                    Some(selected) if selected > particle_index => Some(selected - 1),
+                    selected => selected,
+                };
+            }
+            steps += 1;
+            total_simulation_time += start.elapsed();
+
+            let start = Instant::now();
+            diagnostics.record(first_step + steps, population);
+            total_diagnostics_time += start.elapsed();
+        }
 
         let start = Instant::now();
-        if clear_between_frames {
-            framebuffer.clear();
+        match trails {
+            Trails::Off => framebuffer.clear(),
+            Trails::Fading => framebuffer.fade(trail_decay),
+            Trails::Persistent => {}
         }
         total_clearing_screen_time += start.elapsed();
 
         let start = Instant::now();
-        for (particle, particle_color) in population.iter().zip(particles_colors.iter()) {
-            if particle.mass == 0.0 {
-                continue;
-            }
-            if PARTICLE_SHAPE == "square" {
-                framebuffer.draw_square(
-                    (particle.position[dim_0] * zoom + (SCREEN_WIDTH as f64 / 2f64)) as isize + shift.0,
-                    (particle.position[dim_1] * zoom + (SCREEN_HEIGHT as f64 / 2f64)) as isize + shift.1,
-                    particle.mass.sqrt() as usize,
-                    particle.mass.sqrt() as usize,
+        let particles_colors = coloring::particles_colors(population, color_by, colormap, &random_colors);
+        // Particles close to the viewer are no larger than the screen
+        let max_size = (framebuffer.width() as f64).hypot(framebuffer.height() as f64);
+        // Far to near, so that nearer particles cover farther ones
+        let mut projected_particles: Vec<_> = population
+            .iter()
+            .zip(particles_colors.iter())
+            .filter(|(particle, _)| particle.mass != 0.0)
+            .filter_map(|(particle, particle_color)| {
+                let projected = camera.project(&particle.position, framebuffer.width(), framebuffer.height())?;
+                Some((
+                    projected,
+                    (particle.mass.sqrt() * projected.scale).min(max_size) as usize,
                     particle_color,
-                );
+                ))
+            })
+            .collect();
+        projected_particles.sort_by(|(a, _, _), (b, _, _)| b.depth.total_cmp(&a.depth));
+        for (projected, size, particle_color) in projected_particles {
+            if config.particle_shape == ParticleShape::Square {
+                framebuffer.draw_square(projected.x, projected.y, size, size, particle_color);
             } else {
-                framebuffer.draw_circle(
-                    (particle.position[dim_0] * zoom + (SCREEN_WIDTH as f64 / 2f64)) as isize + shift.0,
-                    (particle.position[dim_1] * zoom + (SCREEN_HEIGHT as f64 / 2f64)) as isize + shift.1,
-                    particle.mass.sqrt() as usize,
-                    particle_color,
-                );
+                framebuffer.draw_circle(projected.x, projected.y, size, particle_color);
+            }
+        }
+        // Overlays are taken off once presented, so that trails left on the screen never include them
+        let mut overlays = vec![];
+        if let Some(particle_index) = selected_particle {
+            overlays.extend(draw_particle_panel(
+                framebuffer,
+                particle_index,
+                &population[particle_index],
+            ));
+        }
+        if show_hud {
+            let mut text = format!(
+                "UPS: {live_ups:.0}\nStep: {}\nParticles: {}\nZoom: {:.3}\nShift: {} {}\nRotation plane: {} {}",
+                first_step + steps,
+                population.iter().filter(|particle| particle.mass != 0f64).count(),
+                camera.zoom,
+                camera.shift.0,
+                camera.shift.1,
+                camera.plane.0,
+                camera.plane.1,
+            );
+            for (name, duration) in [
+                ("Simulation", total_simulation_time),
+                ("Rendering", total_rendering_time),
+                ("Drawing", total_drawing_time),
+                ("Clearing screen", total_clearing_screen_time),
+                ("Input handling", total_input_handling_time),
+                ("Diagnostics", total_diagnostics_time),
+            ] {
+                text += &format!("\n{name} time: {}ms", duration.as_millis());
+            }
+            if color_by != ColorBy::Random {
+                text += &format!("\nColors: {} ({})", color_by.name(), colormap.name());
+            }
+            if trails == Trails::Fading {
+                text += &format!("\nTrail decay: {trail_decay:.3}");
+            }
+            if let Some(position) = camera.position() {
+                text += &format!("\nCamera: {:.0} {:.0} {:.0}", position[0], position[1], position[2]);
+            }
+            if paused {
+                text += "\nPaused";
             }
+            let (width, height) = panel_size(&text);
+            overlays.extend(framebuffer.save(PANEL_MARGIN as isize, PANEL_MARGIN as isize, width, height));
+            draw_panel(framebuffer, PANEL_MARGIN as isize, PANEL_MARGIN as isize, &text);
+        }
+        if framebuffer.draws_cursor() && !mouses.is_empty() {
+            overlays.extend(draw_cursor(framebuffer, cursor));
         }
         total_rendering_time += start.elapsed();
 
         let start = Instant::now();
         framebuffer.draw();
+        // Last drawn first, as overlays may overlap
+        for covered in overlays.iter().rev() {
+            framebuffer.restore(covered);
+        }
         total_drawing_time += start.elapsed();
 
+        live_ups_frames += 1;
+        if live_ups_start.elapsed() >= Duration::from_secs(1) {
+            live_ups = live_ups_frames as f64 / live_ups_start.elapsed().as_secs_f64();
+            live_ups_start = Instant::now();
+            live_ups_frames = 0;
+        }
+
         let update_duration = update_start.elapsed();
-        if DESIRED_UPDATE_DURATION.is_zero() {
-        } else if update_duration < DESIRED_UPDATE_DURATION {
-            sleep(DESIRED_UPDATE_DURATION - update_duration);
+        if desired_ups != 0 {
+            let desired_update_duration = Duration::from_micros(1000000 / desired_ups as u64);
+            if update_duration < desired_update_duration {
+                sleep(desired_update_duration - update_duration);
+            }
         }
     }
 
-    println!("UPS: {}", i as f32 / engine_start_instant.elapsed().as_secs() as f32);
-    println!("Total time: {}ms", engine_start_instant.elapsed().as_millis());
-    println!("Simulation time: {}ms", total_simulation_time.as_millis());
-    println!("Rendering time: {}ms", total_rendering_time.as_millis());
-    println!("Drawing time: {}ms", total_drawing_time.as_millis());
-    println!("Clearing screen time: {}ms", total_clearing_screen_time.as_millis());
-    println!("Input handling time: {}ms", total_input_handling_time.as_millis());
-    println!(
+    framebuffer.release();
+
+    // Printed to stderr so that stdout can carry a recording
+    eprintln!("UPS: {}", i as f32 / engine_start_instant.elapsed().as_secs() as f32);
+    eprintln!("Total time: {}ms", engine_start_instant.elapsed().as_millis());
+    eprintln!("Simulation time: {}ms", total_simulation_time.as_millis());
+    eprintln!("Rendering time: {}ms", total_rendering_time.as_millis());
+    eprintln!("Drawing time: {}ms", total_drawing_time.as_millis());
+    eprintln!("Clearing screen time: {}ms", total_clearing_screen_time.as_millis());
+    eprintln!("Input handling time: {}ms", total_input_handling_time.as_millis());
+    eprintln!("Diagnostics time: {}ms", total_diagnostics_time.as_millis());
+    eprintln!(
         "Unaccounted time: {}ms",
         (engine_start_instant.elapsed()
             - total_simulation_time
             - total_rendering_time
             - total_drawing_time
             - total_input_handling_time
-            - total_clearing_screen_time)
+            - total_clearing_screen_time
+            - total_diagnostics_time)
             .as_millis()
     );
+    eprintln!("Steps simulated: {steps}");
+    eprintln!("Paused: {paused}");
+    match desired_ups {
+        0 => eprintln!("Desired UPS: unlimited"),
+        desired_ups => eprintln!("Desired UPS: {desired_ups}"),
+    }
+    if diagnostics.is_enabled() {
+        eprintln!("{diagnostics}");
+    }
+    snapshot::save_if_requested(
+        config.save_snapshot.as_deref(),
+        &simulation.gravity,
+        population,
+        first_step + steps,
+    );
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::camera::Camera;
+    use crate::framebuffer::dirty::DirtyRows;
+    use crate::framebuffer::ioctl::Bitfield;
+    use crate::framebuffer::{
+        PixelFormat, copy_rows, draw_cursor, draw_particle_panel, faster_ups, longer_trails, pick_particle,
+        shorter_trails, slower_ups,
+    };
+    use crate::physics::test::coordinates;
+    use crate::physics::{Coordinates, Particle};
+    use crate::render::{MemoryBuffer, RenderTarget};
+
+    #[test]
+    fn test_pixel_format() {
+        let bitfield = |offset, length| Bitfield {
+            offset,
+            length,
+            msb_right: 0,
+        };
+        let rgb565 = PixelFormat {
+            bytes_per_pixel: 2,
+            red: bitfield(11, 5),
+            green: bitfield(5, 6),
+            blue: bitfield(0, 5),
+            transp: bitfield(0, 0),
+        };
+        assert_eq!(rgb565.encode(&[255, 0, 255, 255])[..2], [0x1f, 0xf8]);
+        let mut white = [0xff, 0xff];
+        rgb565.fade(&mut white, 127);
+        assert_eq!(white, [0xef, 0x7b]);
+        let bgra8888 = PixelFormat {
+            bytes_per_pixel: 4,
+            red: bitfield(16, 8),
+            green: bitfield(8, 8),
+            blue: bitfield(0, 8),
+            transp: bitfield(24, 8),
+        };
+        assert_eq!(bgra8888.encode(&[1, 2, 3, 4]), [3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn test_desired_ups() {
+        assert_eq!(faster_ups(60), 120);
+        assert_eq!(faster_ups(960), 0);
+        assert_eq!(faster_ups(0), 0);
+        assert_eq!(slower_ups(0), 1000);
+        assert_eq!(slower_ups(60), 30);
+        assert_eq!(slower_ups(1), 1);
+    }
+
+    #[test]
+    fn test_copy_rows() {
+        // Two rows of three 2 bytes pixels, lines being padded to 8 bytes
+        let buffer: Vec<u8> = (1..=16).collect();
+        let mut rows = DirtyRows::new(2);
+        rows.add(1, 0);
+        rows.add(2, 1);
+        let mut memory = [0u8; 16];
+        copy_rows(&mut memory, &buffer, Some(&rows), 8, 2);
+        assert_eq!(memory, [0, 0, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 13, 14, 0, 0]);
+        copy_rows(&mut memory, &buffer, None, 8, 2);
+        assert_eq!(memory.to_vec(), buffer);
+    }
+
+    #[test]
+    fn test_trail_decay() {
+        assert!((longer_trails(0.85) - 0.9).abs() < 1e-9);
+        assert_eq!(longer_trails(0.995), 0.995);
+        assert!((shorter_trails(0.9) - 0.85).abs() < 1e-9);
+        assert_eq!(shorter_trails(0.1), 0f64);
+    }
+
+    #[test]
+    fn test_pick_particle() {
+        let particle = |mass, x, y| Particle {
+            mass,
+            speed: Coordinates::default(),
+            position: coordinates([x, y]),
+        };
+        let population = vec![
+            particle(10f64, 0f64, 0f64),
+            particle(0f64, 25f64, 0f64),
+            particle(10f64, 30f64, 0f64),
+        ];
+        let camera = Camera {
+            zoom: 2f64,
+            ..Camera::default()
+        };
+        // Particles are drawn at x 50, 100 and 110
+        assert_eq!(pick_particle(&population, &camera, 100, 100, (95, 52)), Some(2));
+        assert_eq!(pick_particle(&population, &camera, 100, 100, (70, 50)), Some(0));
+        assert_eq!(pick_particle(&population, &camera, 100, 100, (80, 50)), None);
+    }
+
+    #[test]
+    fn test_overlays() {
+        let mut buffer = MemoryBuffer::new(300, 200);
+        let trail = [255, 0, 0, 255];
+        buffer.draw_square(0, 0, 300, 200, &trail);
+        let particle = Particle {
+            mass: 1f64,
+            speed: Coordinates::default(),
+            position: Coordinates::default(),
+        };
+
+        // What overlays cover is put back once presented, leaving the trails as they were
+        let mut overlays = vec![];
+        overlays.extend(draw_particle_panel(&mut buffer, 3, &particle));
+        overlays.extend(draw_cursor(&mut buffer, (298, 100)));
+        assert_ne!(buffer.pixel(10, 190), trail);
+        assert_ne!(buffer.pixel(298, 100), trail);
+        buffer.draw();
+        for covered in overlays.iter().rev() {
+            buffer.restore(covered);
+        }
+        assert!(buffer.pixels.chunks_exact(4).all(|pixel| pixel == trail));
+    }
 }
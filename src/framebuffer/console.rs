@@ -0,0 +1,52 @@
+// Responsible for keeping the virtual terminal out of the way while drawing to the framebuffer, and for giving it back
+// in a usable state however rengine stops
+
+use crate::framebuffer::ioctl;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_quit(_signal: libc::c_int) {
+    QUIT_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Turn SIGINT and SIGTERM into a request to quit, see `quit_requested`, so that the run loop can stop cleanly
+pub fn handle_quit_signals() {
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        unsafe { libc::signal(signal, request_quit as *const () as libc::sighandler_t) };
+    }
+}
+
+pub fn quit_requested() -> bool {
+    QUIT_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Virtual terminal switched to graphics mode with its cursor hidden, both being restored when dropped
+pub struct Console {
+    tty: File,
+}
+
+impl Console {
+    /// Take over the controlling terminal if it is a virtual terminal, or else the active one. None if neither can be
+    /// used, for instance under a graphical session.
+    pub fn enter_graphics_mode() -> Option<Self> {
+        let mut tty = ["/dev/tty", "/dev/tty0"].into_iter().find_map(|path| {
+            let tty = OpenOptions::new().read(true).write(true).open(path).ok()?;
+            ioctl::get_console_mode(&tty).ok().map(|_| tty)
+        })?;
+        ioctl::set_console_mode(&tty, ioctl::KD_GRAPHICS).ok()?;
+        // Hide the cursor
+        let _ = tty.write_all(b"\x1b[?25l");
+        Some(Console { tty })
+    }
+}
+
+impl Drop for Console {
+    fn drop(&mut self) {
+        let _ = ioctl::set_console_mode(&self.tty, ioctl::KD_TEXT);
+        // Show the cursor and clear what was drawn over the text
+        let _ = self.tty.write_all(b"\x1b[?25h\x1b[2J\x1b[H");
+    }
+}
@@ -0,0 +1,80 @@
+// Responsible for tracking which parts of the back buffer changed, so that presenting a frame only copies those to the
+// device memory. Changes are kept as a span of columns per row, which is cheap to extend pixel by pixel and covers the
+// bounding boxes of particles closely enough.
+
+/// Columns `start..end` of each row which changed, if any
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirtyRows {
+    spans: Vec<Option<(usize, usize)>>,
+}
+
+impl DirtyRows {
+    pub fn new(height: usize) -> Self {
+        DirtyRows {
+            spans: vec![None; height],
+        }
+    }
+
+    /// Mark the pixel at `x`, `y`, which must be within the buffer
+    pub fn add(&mut self, x: usize, y: usize) {
+        let span = &mut self.spans[y];
+        *span = match *span {
+            Some((start, end)) => Some((start.min(x), end.max(x + 1))),
+            None => Some((x, x + 1)),
+        };
+    }
+
+    /// Extend every row by the span of the same row of `other`
+    pub fn merge(&mut self, other: &DirtyRows) {
+        for (span, other_span) in self.spans.iter_mut().zip(other.spans.iter()) {
+            *span = match (*span, *other_span) {
+                (Some((start, end)), Some((other_start, other_end))) => {
+                    Some((start.min(other_start), end.max(other_end)))
+                }
+                (span, None) => span,
+                (None, other_span) => other_span,
+            };
+        }
+    }
+
+    /// Rows `y` with their span `start..end`
+    pub fn spans(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.spans
+            .iter()
+            .enumerate()
+            .filter_map(|(y, span)| span.map(|(start, end)| (y, start, end)))
+    }
+
+    /// Number of pixels within the spans
+    pub fn area(&self) -> usize {
+        self.spans().map(|(_, start, end)| end - start).sum()
+    }
+
+    pub fn reset(&mut self) {
+        self.spans.fill(None);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::framebuffer::dirty::DirtyRows;
+
+    #[test]
+    fn test_dirty_rows() {
+        let mut drawn = DirtyRows::new(4);
+        drawn.add(5, 1);
+        drawn.add(2, 1);
+        drawn.add(0, 3);
+        assert_eq!(drawn.spans().collect::<Vec<_>>(), [(1, 2, 6), (3, 0, 1)]);
+        assert_eq!(drawn.area(), 5);
+
+        let mut cleared = DirtyRows::new(4);
+        cleared.add(8, 1);
+        cleared.add(1, 2);
+        cleared.merge(&drawn);
+        assert_eq!(cleared.spans().collect::<Vec<_>>(), [(1, 2, 9), (2, 1, 2), (3, 0, 1)]);
+
+        cleared.reset();
+        assert_eq!(cleared.area(), 0);
+    }
+}
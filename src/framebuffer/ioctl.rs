@@ -0,0 +1,131 @@
+// Bindings to the parts of the Linux framebuffer (linux/fb.h) and console (linux/kd.h) APIs used by rengine
+
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+
+const FBIOGET_VSCREENINFO: libc::c_ulong = 0x4600;
+const FBIOPUT_VSCREENINFO: libc::c_ulong = 0x4601;
+const FBIOGET_FSCREENINFO: libc::c_ulong = 0x4602;
+const FBIOPAN_DISPLAY: libc::c_ulong = 0x4606;
+const FBIO_WAITFORVSYNC: libc::c_ulong = 0x40044620;
+const KDSETMODE: libc::c_ulong = 0x4B3A;
+const KDGETMODE: libc::c_ulong = 0x4B3B;
+
+pub const KD_TEXT: libc::c_int = 0;
+pub const KD_GRAPHICS: libc::c_int = 1;
+
+/// Position of a color channel within a pixel
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Bitfield {
+    pub offset: u32,
+    pub length: u32,
+    pub msb_right: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VariableScreenInfo {
+    pub xres: u32,
+    pub yres: u32,
+    pub xres_virtual: u32,
+    pub yres_virtual: u32,
+    pub xoffset: u32,
+    pub yoffset: u32,
+    pub bits_per_pixel: u32,
+    pub grayscale: u32,
+    pub red: Bitfield,
+    pub green: Bitfield,
+    pub blue: Bitfield,
+    pub transp: Bitfield,
+    pub nonstd: u32,
+    pub activate: u32,
+    pub height: u32,
+    pub width: u32,
+    pub accel_flags: u32,
+    pub pixclock: u32,
+    pub left_margin: u32,
+    pub right_margin: u32,
+    pub upper_margin: u32,
+    pub lower_margin: u32,
+    pub hsync_len: u32,
+    pub vsync_len: u32,
+    pub sync: u32,
+    pub vmode: u32,
+    pub rotate: u32,
+    pub colorspace: u32,
+    pub reserved: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedScreenInfo {
+    pub id: [u8; 16],
+    pub smem_start: libc::c_ulong,
+    pub smem_len: u32,
+    pub type_: u32,
+    pub type_aux: u32,
+    pub visual: u32,
+    pub xpanstep: u16,
+    pub ypanstep: u16,
+    pub ywrapstep: u16,
+    /// Length of a line in bytes, which may be more than the visible width
+    pub line_length: u32,
+    pub mmio_start: libc::c_ulong,
+    pub mmio_len: u32,
+    pub accel: u32,
+    pub capabilities: u16,
+    pub reserved: [u16; 2],
+}
+
+pub fn get_variable_screen_info(file: &File) -> io::Result<VariableScreenInfo> {
+    let mut info = VariableScreenInfo::default();
+    ioctl(file, FBIOGET_VSCREENINFO, &mut info)?;
+    Ok(info)
+}
+
+/// Drivers may adjust `info` to what they support, so it should be read back afterwards
+pub fn put_variable_screen_info(file: &File, info: &VariableScreenInfo) -> io::Result<()> {
+    let mut info = *info;
+    ioctl(file, FBIOPUT_VSCREENINFO, &mut info)
+}
+
+/// Show the part of the virtual screen starting at the `xoffset` and `yoffset` of `info`
+pub fn pan_display(file: &File, info: &VariableScreenInfo) -> io::Result<()> {
+    let mut info = *info;
+    ioctl(file, FBIOPAN_DISPLAY, &mut info)
+}
+
+/// Block until the next vertical blank of the first display, failing if the driver does not support it
+pub fn wait_for_vsync(file: &File) -> io::Result<()> {
+    let mut crtc: u32 = 0;
+    ioctl(file, FBIO_WAITFORVSYNC, &mut crtc)
+}
+
+pub fn get_fixed_screen_info(file: &File) -> io::Result<FixedScreenInfo> {
+    let mut info = FixedScreenInfo::default();
+    ioctl(file, FBIOGET_FSCREENINFO, &mut info)?;
+    Ok(info)
+}
+
+/// Fails if `file` is not a virtual terminal
+pub fn get_console_mode(file: &File) -> io::Result<libc::c_int> {
+    let mut mode: libc::c_int = 0;
+    ioctl(file, KDGETMODE, &mut mode)?;
+    Ok(mode)
+}
+
+pub fn set_console_mode(file: &File, mode: libc::c_int) -> io::Result<()> {
+    // The mode is passed by value rather than through a pointer
+    ioctl(file, KDSETMODE, mode as usize as *mut libc::c_void)
+}
+
+fn ioctl<T>(file: &File, request: libc::c_ulong, argument: *mut T) -> io::Result<()> {
+    // The request type differs between libc implementations, hence the inferred cast
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, argument) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+// Responsible for building populations modelled on astrophysical systems, with speeds in equilibrium for the gravity
+// they are simulated with. Systems without a preferred plane fill all DIMENSIONS dimensions, while disks and orbits
+// lie in the plane of the first two axes.
+
+use crate::physics::diagnostics::Diagnostics;
+use crate::physics::{Coordinates, DIMENSIONS, Gravity, Particle, Population, Solver, distance_squared};
+use rand::Rng;
+use std::f64::consts::TAU;
+
+/// Standard normal value, by the Box-Muller transform
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u: f64 = 1f64 - rng.random::<f64>();
+    (-2f64 * u.ln()).sqrt() * (TAU * rng.random::<f64>()).cos()
+}
+
+/// Unit vector pointing in a uniformly random direction
+fn random_direction(rng: &mut impl Rng) -> Coordinates {
+    loop {
+        let direction = Coordinates::new(std::array::from_fn(|_| gaussian(rng)));
+        let norm = distance_squared(direction, Coordinates::default()).sqrt();
+        if norm > 0f64 {
+            return scaled(direction, 1f64 / norm);
+        }
+    }
+}
+
+fn scaled(mut coordinates: Coordinates, factor: f64) -> Coordinates {
+    for i in 0..DIMENSIONS {
+        coordinates[i] *= factor;
+    }
+    coordinates
+}
+
+/// Point of the plane of the first two axes at `radius` from the origin and `angle` from the first axis
+fn in_plane(radius: f64, angle: f64) -> Coordinates {
+    let mut coordinates = Coordinates::default();
+    coordinates[0] = radius * angle.cos();
+    coordinates[1] = radius * angle.sin();
+    coordinates
+}
+
+/// Move the center of mass to the origin and cancel the total momentum, so that the system stays in place
+fn center(population: &mut Population) {
+    let mass: f64 = population.iter().map(|particle| particle.mass).sum();
+    if mass == 0f64 {
+        return;
+    }
+    for i in 0..DIMENSIONS {
+        let position = population
+            .iter()
+            .map(|particle| particle.mass * particle.position[i])
+            .sum::<f64>()
+            / mass;
+        let speed = population
+            .iter()
+            .map(|particle| particle.mass * particle.speed[i])
+            .sum::<f64>()
+            / mass;
+        for particle in population.iter_mut() {
+            particle.position[i] -= position;
+            particle.speed[i] -= speed;
+        }
+    }
+}
+
+/// Scale speeds so that twice the kinetic energy balances the potential energy, as in a system which neither
+/// collapses nor expands on average
+fn virialize(population: &mut Population, g: f64) {
+    let gravity = Gravity {
+        g,
+        minimal_distance: 0f64,
+        solver: Solver::BruteForce,
+    };
+    let diagnostics = Diagnostics::new(population, &gravity);
+    if diagnostics.kinetic_energy == 0f64 {
+        return;
+    }
+    let factor = (-diagnostics.potential_energy / (2f64 * diagnostics.kinetic_energy)).sqrt();
+    for particle in population.iter_mut() {
+        particle.speed = scaled(particle.speed, factor);
+    }
+}
+
+/// Plummer sphere of `pop_size` particles of `total_mass` with a core of `scale_radius`, its speeds being drawn from
+/// the Plummer distribution (Aarseth, Hénon and Wielen, 1974) then scaled to virial equilibrium
+pub fn plummer(pop_size: usize, total_mass: f64, scale_radius: f64, g: f64, rng: &mut impl Rng) -> Population {
+    let mass = total_mass / pop_size as f64;
+    let mut population: Population = (0..pop_size)
+        .map(|_| {
+            // Radius enclosing a uniformly drawn fraction of the mass, the farthest tail being cut off
+            let mass_fraction: f64 = rng.random_range(0.001..0.99);
+            let radius = scale_radius / (mass_fraction.powf(-2f64 / 3f64) - 1f64).sqrt();
+            // Fraction of the escape speed, by rejection sampling of q² (1 - q²)^7/2
+            let speed_fraction = loop {
+                let q: f64 = rng.random();
+                if rng.random::<f64>() * 0.1 < q * q * (1f64 - q * q).powf(3.5) {
+                    break q;
+                }
+            };
+            let escape_speed = (2f64 * g * total_mass / (radius * radius + scale_radius * scale_radius).sqrt()).sqrt();
+            Particle {
+                mass,
+                speed: scaled(random_direction(rng), speed_fraction * escape_speed),
+                position: scaled(random_direction(rng), radius),
+            }
+        })
+        .collect();
+    center(&mut population);
+    virialize(&mut population, g);
+    population
+}
+
+/// Particles at rest spread uniformly within a ball of `radius`, which collapses under its own gravity
+pub fn cold_collapse(pop_size: usize, total_mass: f64, radius: f64, rng: &mut impl Rng) -> Population {
+    let mass = total_mass / pop_size as f64;
+    let mut population: Population = (0..pop_size)
+        .map(|_| Particle {
+            mass,
+            speed: Coordinates::default(),
+            position: scaled(
+                random_direction(rng),
+                radius * rng.random::<f64>().powf(1f64 / DIMENSIONS as f64),
+            ),
+        })
+        .collect();
+    center(&mut population);
+    population
+}
+
+/// Rotating disk whose surface density decreases exponentially with the distance to a central mass
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Disk {
+    /// Number of particles besides the central one
+    pub particles: usize,
+    pub mass: f64,
+    /// Distance over which the density is divided by e
+    pub scale_length: f64,
+    /// Mass of the particle at the center, none if 0
+    pub central_mass: f64,
+}
+
+/// `disk` around the origin, rotating counterclockwise with each particle on a circular orbit around the mass closer
+/// to the center than itself
+pub fn exponential_disk(disk: &Disk, g: f64, rng: &mut impl Rng) -> Population {
+    let mass = disk.mass / disk.particles as f64;
+    // The distance to the center of an exponential disk follows a gamma distribution of shape 2
+    let mut radii: Vec<f64> = (0..disk.particles)
+        .map(|_| -disk.scale_length * ((1f64 - rng.random::<f64>()) * (1f64 - rng.random::<f64>())).ln())
+        .collect();
+    radii.sort_by(f64::total_cmp);
+    let mut population = Vec::with_capacity(disk.particles + 1);
+    if disk.central_mass != 0f64 {
+        population.push(Particle {
+            mass: disk.central_mass,
+            speed: Coordinates::default(),
+            position: Coordinates::default(),
+        });
+    }
+    for (index, radius) in radii.into_iter().enumerate() {
+        let enclosed_mass = disk.central_mass + mass * index as f64;
+        let angle = rng.random_range(0f64..TAU);
+        population.push(Particle {
+            mass,
+            speed: in_plane((g * enclosed_mass / radius).sqrt(), angle + TAU / 4f64),
+            position: in_plane(radius, angle),
+        });
+    }
+    center(&mut population);
+    population
+}
+
+/// Star of `star_mass` at the center of `planets` planets of `planet_mass`, on circular orbits evenly spread from
+/// `inner_radius` to `outer_radius` on a logarithmic scale. Each planet gets the speed of a circular orbit around the
+/// star alone, pulls between planets being ignored.
+pub fn kepler(
+    planets: usize,
+    star_mass: f64,
+    planet_mass: f64,
+    inner_radius: f64,
+    outer_radius: f64,
+    g: f64,
+    rng: &mut impl Rng,
+) -> Population {
+    let mut population = vec![Particle {
+        mass: star_mass,
+        speed: Coordinates::default(),
+        position: Coordinates::default(),
+    }];
+    for planet in 0..planets {
+        let fraction = if planets > 1 {
+            planet as f64 / (planets - 1) as f64
+        } else {
+            0f64
+        };
+        let radius = inner_radius * (outer_radius / inner_radius).powf(fraction);
+        let angle = rng.random_range(0f64..TAU);
+        population.push(Particle {
+            mass: planet_mass,
+            // Relative speed of a two-body circular orbit, the star being at rest until the system is centered
+            speed: in_plane((g * (star_mass + planet_mass) / radius).sqrt(), angle + TAU / 4f64),
+            position: in_plane(radius, angle),
+        });
+    }
+    // The star moves against the planets, keeping the system in place
+    center(&mut population);
+    population
+}
+
+/// Two copies of `disk` `separation` apart along the first axis, heading toward each other at `relative_speed`. The
+/// second one is offset by a scale length along the second axis, so that the galaxies graze rather than collide
+/// head-on.
+pub fn colliding_galaxies(disk: &Disk, separation: f64, relative_speed: f64, g: f64, rng: &mut impl Rng) -> Population {
+    let mut population = Vec::with_capacity(2 * (disk.particles + 1));
+    for (side, offset) in [(-1f64, 0f64), (1f64, disk.scale_length)] {
+        for mut particle in exponential_disk(disk, g, rng) {
+            particle.position[0] += side * separation / 2f64;
+            particle.position[1] += offset;
+            particle.speed[0] -= side * relative_speed / 2f64;
+            population.push(particle);
+        }
+    }
+    center(&mut population);
+    population
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::generators::{Disk, cold_collapse, colliding_galaxies, exponential_disk, kepler, plummer};
+    use crate::physics::diagnostics::Diagnostics;
+    use crate::physics::{Coordinates, Gravity, Solver, distance_squared};
+    use crate::random::SplitMix64;
+
+    #[test]
+    fn test_generators() {
+        let mut rng = SplitMix64::new(0);
+        let gravity = Gravity {
+            g: 2f64,
+            minimal_distance: 0f64,
+            solver: Solver::BruteForce,
+        };
+        let norm = |coordinates: Coordinates| distance_squared(coordinates, Coordinates::default()).sqrt();
+
+        let population = plummer(200, 1000f64, 50f64, gravity.g, &mut rng);
+        let diagnostics = Diagnostics::new(&population, &gravity);
+        assert!((diagnostics.mass - 1000f64).abs() < 1e-9);
+        assert!((2f64 * diagnostics.kinetic_energy + diagnostics.potential_energy).abs() < 1e-6);
+        assert!(norm(diagnostics.center_of_mass) < 1e-9);
+        assert!(norm(diagnostics.momentum) < 1e-9);
+
+        let population = cold_collapse(100, 1000f64, 50f64, &mut rng);
+        assert!(population.iter().all(|particle| norm(particle.position) < 100f64));
+        assert_eq!(Diagnostics::new(&population, &gravity).kinetic_energy, 0f64);
+
+        let relative = |a: Coordinates, b: Coordinates| distance_squared(a, b).sqrt();
+        let population = kepler(5, 1000f64, 1f64, 10f64, 160f64, gravity.g, &mut rng);
+        assert!((relative(population[5].position, population[0].position) - 160f64).abs() < 1e-9);
+        // Circular two-body orbit of a heavy planet: the centripetal acceleration of the relative motion is the
+        // gravity of both masses
+        let population = kepler(1, 1000f64, 500f64, 100f64, 100f64, gravity.g, &mut rng);
+        let radius = relative(population[1].position, population[0].position);
+        let speed = relative(population[1].speed, population[0].speed);
+        assert!((speed.powi(2) / radius - gravity.g * 1500f64 / radius.powi(2)).abs() < 1e-9);
+
+        let disk = Disk {
+            particles: 100,
+            mass: 100f64,
+            scale_length: 20f64,
+            central_mass: 1000f64,
+        };
+        let population = exponential_disk(&disk, gravity.g, &mut rng);
+        assert_eq!(population.len(), 101);
+        let angular_momentum = Diagnostics::new(&population, &gravity).angular_momentum[0];
+        assert!(angular_momentum > 0f64);
+
+        let population = colliding_galaxies(&disk, 400f64, 3f64, gravity.g, &mut rng);
+        assert_eq!(population.len(), 202);
+        let left = Diagnostics::new(&population[..101].to_vec(), &gravity);
+        let right = Diagnostics::new(&population[101..].to_vec(), &gravity);
+        assert!((right.center_of_mass[0] - left.center_of_mass[0] - 400f64).abs() < 1e-9);
+        assert!(((left.momentum[0] - right.momentum[0]) / left.mass - 3f64).abs() < 1e-9);
+    }
+}
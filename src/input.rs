@@ -0,0 +1,131 @@
+// Responsible for reading keyboards and mouses through the Linux input event devices, following them as they are
+// plugged and unplugged
+
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::mem::transmute;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Where udev links every input device under a stable name
+pub const INPUT_DEVICES_DIRECTORY: &str = "/dev/input/by-id";
+
+// How often the directory is scanned for devices plugged in since the last time
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Event as read from a Linux input device, see linux/input.h
+#[repr(C)]
+#[derive(Debug)]
+pub struct InputEvent {
+    pub time: [u64; 2],
+    pub type_: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+/// Every device of a type, such as `kbd` or `mouse`, currently plugged in
+pub struct InputDevices {
+    directory: PathBuf,
+    device_type: String,
+    devices: Vec<(PathBuf, File)>,
+    last_scan: Instant,
+}
+
+impl InputDevices {
+    pub fn new(directory: &Path, device_type: &str) -> Self {
+        let mut input_devices = InputDevices {
+            directory: directory.to_path_buf(),
+            device_type: device_type.to_string(),
+            devices: vec![],
+            last_scan: Instant::now(),
+        };
+        input_devices.scan();
+        input_devices
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    // Open the devices which appeared and forget the ones which disappeared
+    fn scan(&mut self) {
+        let suffix = [b"event-", self.device_type.as_bytes()].concat();
+        let paths: Vec<PathBuf> = fs::read_dir(&self.directory)
+            .into_iter()
+            .flat_map(|entries| entries.flatten())
+            .filter(|entry| entry.file_name().as_bytes().ends_with(&suffix))
+            .map(|entry| entry.path())
+            .collect();
+        self.devices.retain(|(path, _)| paths.contains(path));
+        for path in paths {
+            if self.devices.iter().all(|(opened_path, _)| *opened_path != path) {
+                // Non blocking
+                if let Ok(file) = OpenOptions::new().read(true).custom_flags(0x800).open(&path) {
+                    self.devices.push((path, file));
+                }
+            }
+        }
+        self.last_scan = Instant::now();
+    }
+
+    /// Events received since the last call, devices failing to be read being dropped until plugged in again
+    pub fn read_events(&mut self) -> Vec<InputEvent> {
+        if self.last_scan.elapsed() >= RESCAN_INTERVAL {
+            self.scan();
+        }
+        let mut events = vec![];
+        self.devices.retain_mut(|(_, file)| {
+            let mut buffer = [0u8; 24];
+            loop {
+                match file.read(&mut buffer) {
+                    Ok(24) => events.push(unsafe { transmute::<[u8; 24], InputEvent>(buffer) }),
+                    Ok(_) => return true,
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+                    Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => return false,
+                }
+            }
+        });
+        events
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::input::InputDevices;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_input_devices() {
+        let directory = env::temp_dir().join(format!("rengine-input-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut devices = InputDevices::new(&directory, "kbd");
+        assert!(devices.is_empty());
+
+        // Key Q pressed
+        let mut event = [0u8; 24];
+        event[16..18].copy_from_slice(&1u16.to_le_bytes());
+        event[18..20].copy_from_slice(&16u16.to_le_bytes());
+        event[20..24].copy_from_slice(&1i32.to_le_bytes());
+        fs::write(directory.join("usb-keyboard-event-kbd"), event).unwrap();
+        fs::write(directory.join("usb-mouse-event-mouse"), event).unwrap();
+        devices.scan();
+        assert_eq!(devices.len(), 1);
+        let events = devices.read_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].type_, events[0].code, events[0].value), (1, 16, 1));
+
+        fs::remove_file(directory.join("usb-keyboard-event-kbd")).unwrap();
+        devices.scan();
+        assert!(devices.is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
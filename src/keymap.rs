@@ -0,0 +1,291 @@
+// Responsible for mapping keys to what they do, so that layouts other than QWERTY can be used. A keymap file has one
+// binding per line, an evdev key name then an action:
+//
+// KEY_A=quit
+// KEY_LEFT=pan-left
+//
+// Such a file replaces the default bindings entirely.
+
+use crate::config::ConfigError;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    RotateDimensions,
+    NextRotationPlane,
+    RotateForward,
+    RotateBackward,
+    ResetCamera,
+    /// Go through no trails, fading trails and persistent trails
+    ToggleTrails,
+    LongerTrails,
+    ShorterTrails,
+    /// Color particles by the next quantity, see `ColorBy`
+    CycleColoring,
+    CycleColormap,
+    ToggleHud,
+    TogglePause,
+    Step,
+    SlowerUps,
+    FasterUps,
+    Quit,
+}
+
+const ACTIONS: [(&str, Action); 22] = [
+    ("pan-left", Action::PanLeft),
+    ("pan-right", Action::PanRight),
+    ("pan-up", Action::PanUp),
+    ("pan-down", Action::PanDown),
+    ("zoom-in", Action::ZoomIn),
+    ("zoom-out", Action::ZoomOut),
+    ("rotate-dimensions", Action::RotateDimensions),
+    ("next-rotation-plane", Action::NextRotationPlane),
+    ("rotate-forward", Action::RotateForward),
+    ("rotate-backward", Action::RotateBackward),
+    ("reset-camera", Action::ResetCamera),
+    ("toggle-trails", Action::ToggleTrails),
+    ("longer-trails", Action::LongerTrails),
+    ("shorter-trails", Action::ShorterTrails),
+    ("cycle-coloring", Action::CycleColoring),
+    ("cycle-colormap", Action::CycleColormap),
+    ("toggle-hud", Action::ToggleHud),
+    ("toggle-pause", Action::TogglePause),
+    ("step", Action::Step),
+    ("slower-ups", Action::SlowerUps),
+    ("faster-ups", Action::FasterUps),
+    ("quit", Action::Quit),
+];
+
+impl Action {
+    /// Whether a key event of `value`, 0 for a release, 1 for a press and 2 for a repeat, triggers the action
+    pub fn is_triggered_by(&self, value: i32) -> bool {
+        match self {
+            // Moving the view and quitting react to every event, as they always have
+            Action::PanLeft | Action::PanRight | Action::PanUp | Action::PanDown => true,
+            Action::ZoomIn | Action::ZoomOut | Action::Quit => true,
+            // Repeated while held
+            Action::Step | Action::RotateForward | Action::RotateBackward => value != 0,
+            _ => value == 1,
+        }
+    }
+}
+
+// Names of linux/input-event-codes.h for the keys of a usual keyboard
+const KEY_NAMES: [(&str, u16); 98] = [
+    ("KEY_ESC", 1),
+    ("KEY_1", 2),
+    ("KEY_2", 3),
+    ("KEY_3", 4),
+    ("KEY_4", 5),
+    ("KEY_5", 6),
+    ("KEY_6", 7),
+    ("KEY_7", 8),
+    ("KEY_8", 9),
+    ("KEY_9", 10),
+    ("KEY_0", 11),
+    ("KEY_MINUS", 12),
+    ("KEY_EQUAL", 13),
+    ("KEY_BACKSPACE", 14),
+    ("KEY_TAB", 15),
+    ("KEY_Q", 16),
+    ("KEY_W", 17),
+    ("KEY_E", 18),
+    ("KEY_R", 19),
+    ("KEY_T", 20),
+    ("KEY_Y", 21),
+    ("KEY_U", 22),
+    ("KEY_I", 23),
+    ("KEY_O", 24),
+    ("KEY_P", 25),
+    ("KEY_LEFTBRACE", 26),
+    ("KEY_RIGHTBRACE", 27),
+    ("KEY_ENTER", 28),
+    ("KEY_LEFTCTRL", 29),
+    ("KEY_A", 30),
+    ("KEY_S", 31),
+    ("KEY_D", 32),
+    ("KEY_F", 33),
+    ("KEY_G", 34),
+    ("KEY_H", 35),
+    ("KEY_J", 36),
+    ("KEY_K", 37),
+    ("KEY_L", 38),
+    ("KEY_SEMICOLON", 39),
+    ("KEY_APOSTROPHE", 40),
+    ("KEY_GRAVE", 41),
+    ("KEY_LEFTSHIFT", 42),
+    ("KEY_BACKSLASH", 43),
+    ("KEY_Z", 44),
+    ("KEY_X", 45),
+    ("KEY_C", 46),
+    ("KEY_V", 47),
+    ("KEY_B", 48),
+    ("KEY_N", 49),
+    ("KEY_M", 50),
+    ("KEY_COMMA", 51),
+    ("KEY_DOT", 52),
+    ("KEY_SLASH", 53),
+    ("KEY_RIGHTSHIFT", 54),
+    ("KEY_KPASTERISK", 55),
+    ("KEY_LEFTALT", 56),
+    ("KEY_SPACE", 57),
+    ("KEY_CAPSLOCK", 58),
+    ("KEY_F1", 59),
+    ("KEY_F2", 60),
+    ("KEY_F3", 61),
+    ("KEY_F4", 62),
+    ("KEY_F5", 63),
+    ("KEY_F6", 64),
+    ("KEY_F7", 65),
+    ("KEY_F8", 66),
+    ("KEY_F9", 67),
+    ("KEY_F10", 68),
+    ("KEY_KP7", 71),
+    ("KEY_KP8", 72),
+    ("KEY_KP9", 73),
+    ("KEY_KPMINUS", 74),
+    ("KEY_KP4", 75),
+    ("KEY_KP5", 76),
+    ("KEY_KP6", 77),
+    ("KEY_KPPLUS", 78),
+    ("KEY_KP1", 79),
+    ("KEY_KP2", 80),
+    ("KEY_KP3", 81),
+    ("KEY_KP0", 82),
+    ("KEY_KPDOT", 83),
+    ("KEY_102ND", 86),
+    ("KEY_F11", 87),
+    ("KEY_F12", 88),
+    ("KEY_KPENTER", 96),
+    ("KEY_RIGHTCTRL", 97),
+    ("KEY_KPSLASH", 98),
+    ("KEY_RIGHTALT", 100),
+    ("KEY_HOME", 102),
+    ("KEY_UP", 103),
+    ("KEY_PAGEUP", 104),
+    ("KEY_LEFT", 105),
+    ("KEY_RIGHT", 106),
+    ("KEY_END", 107),
+    ("KEY_DOWN", 108),
+    ("KEY_PAGEDOWN", 109),
+    ("KEY_INSERT", 110),
+    ("KEY_DELETE", 111),
+];
+
+/// Actions bound to evdev key codes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(u16, Action)>,
+}
+
+impl Default for Keymap {
+    /// QWERTY layout
+    fn default() -> Self {
+        Keymap {
+            bindings: vec![
+                (105, Action::PanLeft),
+                (106, Action::PanRight),
+                (103, Action::PanUp),
+                (108, Action::PanDown),
+                (13, Action::ZoomIn),
+                (12, Action::ZoomOut),
+                (19, Action::RotateDimensions),
+                (25, Action::NextRotationPlane),
+                (52, Action::RotateForward),
+                (51, Action::RotateBackward),
+                (102, Action::ResetCamera),
+                (20, Action::ToggleTrails),
+                (11, Action::LongerTrails),
+                (10, Action::ShorterTrails),
+                (46, Action::CycleColoring),
+                (47, Action::CycleColormap),
+                (35, Action::ToggleHud),
+                (57, Action::TogglePause),
+                (49, Action::Step),
+                (26, Action::SlowerUps),
+                (27, Action::FasterUps),
+                (16, Action::Quit),
+            ],
+        }
+    }
+}
+
+impl Keymap {
+    pub fn load_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|err| ConfigError::Unreadable {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+        Self::parse(&content, path)
+    }
+
+    // Blank lines and lines starting with # are ignored
+    fn parse(content: &str, path: &Path) -> Result<Self, ConfigError> {
+        let mut bindings = Vec::new();
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key_name, action_name) = line.split_once('=').ok_or_else(|| ConfigError::Malformed {
+                path: path.to_path_buf(),
+                line: line_index + 1,
+            })?;
+            let (key_name, action_name) = (key_name.trim(), action_name.trim());
+            let code = KEY_NAMES
+                .iter()
+                .find(|(name, _)| *name == key_name)
+                .map(|(_, code)| *code)
+                .ok_or_else(|| ConfigError::UnknownKey(key_name.to_string()))?;
+            let action = ACTIONS
+                .iter()
+                .find(|(name, _)| *name == action_name)
+                .map(|(_, action)| *action)
+                .ok_or_else(|| ConfigError::InvalidValue {
+                    key: key_name.to_string(),
+                    value: action_name.to_string(),
+                    expected: ACTIONS.map(|(name, _)| name).join(", "),
+                })?;
+            bindings.retain(|(bound_code, _)| *bound_code != code);
+            bindings.push((code, action));
+        }
+        Ok(Keymap { bindings })
+    }
+
+    /// Action bound to the key of evdev `code`, if any
+    pub fn action(&self, code: u16) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound_code, _)| *bound_code == code)
+            .map(|(_, action)| *action)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::config::ConfigError;
+    use crate::keymap::{Action, Keymap};
+    use std::path::Path;
+
+    #[test]
+    fn test_keymap() {
+        assert_eq!(Keymap::default().action(16), Some(Action::Quit));
+
+        let keymap = Keymap::parse("# AZERTY\nKEY_A = quit\nKEY_Z=pan-up\n", Path::new("azerty.keymap")).unwrap();
+        assert_eq!(keymap.action(30), Some(Action::Quit));
+        assert_eq!(keymap.action(44), Some(Action::PanUp));
+        assert_eq!(keymap.action(16), None);
+
+        let error = Keymap::parse("KEY_A=jump", Path::new("bad.keymap")).unwrap_err();
+        assert!(matches!(error, ConfigError::InvalidValue { ref key, .. } if key == "KEY_A"));
+        let error = Keymap::parse("KEY_NOPE=quit", Path::new("bad.keymap")).unwrap_err();
+        assert_eq!(error, ConfigError::UnknownKey("KEY_NOPE".to_string()));
+    }
+}
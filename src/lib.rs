@@ -1,3 +1,16 @@
+pub mod camera;
+pub mod coloring;
+pub mod config;
+pub mod font;
 pub mod framebuffer;
+pub mod generators;
+pub mod input;
+pub mod keymap;
 pub mod physics;
+pub mod random;
 pub mod raw_engine;
+pub mod render;
+pub mod scenario;
+pub mod snapshot;
+pub mod terminal;
+pub mod video;
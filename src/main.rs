@@ -1,24 +1,100 @@
+use rengine::config::Config;
+use std::env;
+use std::process::exit;
+
+// Settings given on the command line, see `Config::from_args`. The seed is printed for the run to be replayable.
+fn config() -> Config {
+    let config = Config::from_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        exit(2)
+    });
+    eprintln!("Seed: {}", config.seed);
+    config
+}
+
 #[cfg(feature = "benchmark")]
 fn main() {
-    use rengine::physics::Particle;
-    use rengine::raw_engine;
-    raw_engine::run(Particle::new_random_pop_in_screen(2560, 1440));
+    use rengine::random::SplitMix64;
+    use rengine::scenario::DEFAULT_SCREEN_SIZE;
+    use rengine::{raw_engine, scenario, snapshot};
+    let config = config();
+    let mut simulation = config.simulation();
+    let mut rng = SplitMix64::new(config.seed);
+    let scenario = scenario::from_name(&config.scenario).expect("Scenario is checked when set");
+    let (width, height) = DEFAULT_SCREEN_SIZE;
+    let snapshot = snapshot::load_or(config.load_snapshot.as_deref(), &simulation.gravity, || {
+        scenario.population(&config, width, height, &mut rng)
+    });
+    raw_engine::run(snapshot.population, &mut simulation, &config, snapshot.step);
 }
 
 #[cfg(feature = "framebuffer")]
 fn main() {
-    use rengine::framebuffer;
-    use rengine::physics::Particle;
-    framebuffer::run(&mut Particle::new_random_pop_in_screen(2560, 1440));
+    use rengine::config::Renderer;
+    use rengine::framebuffer::Framebuffer;
+    use rengine::random::SplitMix64;
+    use rengine::render::RenderTarget;
+    use rengine::terminal::Terminal;
+    use rengine::video::VideoRecorder;
+    use rengine::{framebuffer, scenario, snapshot};
+
+    fn run(render_target: &mut impl RenderTarget, config: &Config) {
+        let mut simulation = config.simulation();
+        let mut rng = SplitMix64::new(config.seed);
+        let scenario = scenario::from_name(&config.scenario).expect("Scenario is checked when set");
+        let (width, height) = (render_target.width(), render_target.height());
+        let mut snapshot = snapshot::load_or(config.load_snapshot.as_deref(), &simulation.gravity, || {
+            scenario.population(config, width, height, &mut rng)
+        });
+        framebuffer::run(
+            render_target,
+            &mut snapshot.population,
+            &mut simulation,
+            config,
+            snapshot.step,
+            scenario.camera(config, width, height),
+            &mut rng,
+        );
+    }
+
+    let config = config();
+    match (&config.record, config.renderer) {
+        (Some(path), _) => run(
+            &mut VideoRecorder::create(
+                path,
+                config.record_format,
+                config.record_width,
+                config.record_height,
+                config.record_fps,
+            ),
+            &config,
+        ),
+        (None, Renderer::Framebuffer) => run(&mut Framebuffer::new(config.vsync), &config),
+        (None, Renderer::Terminal(characters)) => run(&mut Terminal::new(characters), &config),
+    }
 }
 
+// Expects the settings of framebuffer_test.env run for 1500 iterations:
+// rengine --config framebuffer_test.env --iterations 1500
 #[cfg(feature = "e2e-test")]
 fn main() {
     use rengine::framebuffer;
     use rengine::physics::Coordinates;
     use rengine::physics::Particle;
-    let mut pop = Particle::new_test_pop();
-    framebuffer::run(&mut pop);
+    use rengine::random::SplitMix64;
+    use rengine::render::MemoryBuffer;
+    use rengine::scenario;
+    let config = config();
+    let mut pop = scenario::three_body();
+    framebuffer::run(
+        &mut MemoryBuffer::new(2560, 1440),
+        &mut pop,
+        &mut config.simulation(),
+        &config,
+        0,
+        config.camera(1440),
+        &mut SplitMix64::new(config.seed),
+    );
     assert_eq!(
         pop[..3],
         [
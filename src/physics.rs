@@ -1,10 +1,11 @@
 // Responsible for defining newtonian physic
 
-use proc_macros::{
-    distance_squared as distance_squared_macro, get_default_particle_mass_from_env_var, get_dimensions_from_env_var,
-    get_g_from_env_var, get_minimal_distance_from_env_var, get_pop_size_from_env_var,
-};
-use rand::Rng;
+pub mod barnes_hut;
+pub mod diagnostics;
+pub mod integrators;
+
+use crate::physics::integrators::Integrator;
+use proc_macros::{distance_squared as distance_squared_macro, get_dimensions_from_env_var};
 use rayon::iter::IndexedParallelIterator;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 use std::array;
@@ -38,22 +39,45 @@ impl IndexMut<usize> for Coordinates {
 
 const DEFAULT_COORDINATES: Coordinates = Coordinates([0f64; DIMENSIONS]);
 
-pub const POP_SIZE: usize = get_pop_size_from_env_var!();
-
-pub const G: f64 = get_g_from_env_var!();
+/// Algorithm used to compute the gravitational forces between particles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Solver {
+    /// Compare every particle against every other particle
+    BruteForce,
+    /// Approximate groups of distant particles by their centre of mass. The smaller the opening angle `theta`, the
+    /// more accurate and the slower the approximation. With a `theta` of 0 no group is ever approximated.
+    BarnesHut { theta: f64 },
+}
 
-pub const MINIMAL_DISTANCE: f64 = get_minimal_distance_from_env_var!();
-const MINIMAL_DISTANCE_SQUARED: f64 = MINIMAL_DISTANCE * MINIMAL_DISTANCE;
+/// Law of gravitation between particles, and how it is computed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gravity {
+    pub g: f64,
+    /// Particles closer than this distance are merged
+    pub minimal_distance: f64,
+    pub solver: Solver,
+}
 
-const DEFAULT_PARTICLE: Particle = Particle {
-    mass: 0f64,
-    speed: DEFAULT_COORDINATES,
-    position: DEFAULT_COORDINATES,
-};
+/// Everything needed to advance a population in time
+pub struct Simulation {
+    pub gravity: Gravity,
+    pub integrator: Box<dyn Integrator>,
+    /// Simulated time elapsed during one step
+    pub dt: f64,
+}
 
-pub type Population = [Particle; POP_SIZE];
+impl Simulation {
+    /// Advance the population by one step. Particles absorbed by a merge are removed, and the indices they had before
+    /// the step are returned in ascending order so that callers can keep their own per-particle data in sync.
+    pub fn step(&mut self, population: &mut Population) -> Vec<usize> {
+        let to_merge = self.integrator.step(population, &self.gravity, self.dt);
+        merge_particles(population, &to_merge);
+        remove_massless_particles(population)
+    }
+}
 
-const DEFAULT_POP: Population = [DEFAULT_PARTICLE; POP_SIZE];
+// Stored on the heap so that its size can be chosen at runtime without risking a stack overflow
+pub type Population = Vec<Particle>;
 
 #[derive(Clone, Copy, Default, Debug, PartialEq)]
 pub struct Particle {
@@ -62,107 +86,46 @@ pub struct Particle {
     pub position: Coordinates,
 }
 
-impl Particle {
-    fn new_random() -> Self {
-        let mut rng = rand::rng();
-        Self {
-            mass: 1f64,
-            speed: Coordinates([0f64; DIMENSIONS]),
-            position: Coordinates(rng.random()),
-        }
-    }
-
-    pub fn new_random_pop() -> Population {
-        let mut pop = DEFAULT_POP;
-        for slot in pop.iter_mut() {
-            *slot = Self::new_random();
-        }
-        pop
-    }
-
-    pub fn new_random_pop_in_screen(width: u32, height: u32) -> Population {
-        let mut rng = rand::rng();
-        let mut pop = DEFAULT_POP;
-        let half_width = width as f64 / 2f64;
-        let half_height = height as f64 / 2f64;
-        for slot in pop.iter_mut() {
-            let mut position = DEFAULT_COORDINATES;
-            position[0] = rng.random_range((-half_width)..half_width);
-            position[1] = rng.random_range((-half_height)..half_height);
-            for position in position.0.iter_mut().skip(2) {
-                *position = rng.random_range(-100.0..100.0);
-            }
-            *slot = Self {
-                mass: get_default_particle_mass_from_env_var!(),
-                speed: DEFAULT_COORDINATES,
-                position,
-            };
-        }
-        pop
-    }
-
-    pub fn new_test_pop() -> Population {
-        let mut pop = DEFAULT_POP;
-        if POP_SIZE < 3 {
-            panic!("POP_SIZE must be 3 for test")
-        } else if DIMENSIONS != 2 {
-            panic!("DIMENSIONS must be 2 for test")
-        } else {
-            pop[0] = Particle {
-                mass: 15f64,
-                speed: DEFAULT_COORDINATES,
-                position: {
-                    let mut position = DEFAULT_COORDINATES;
-                    position[0] = 100f64;
-                    position[1] = 100f64;
-                    position
-                },
-            };
-            pop[1] = Particle {
-                mass: 10f64,
-                speed: DEFAULT_COORDINATES,
-                position: {
-                    let mut position = DEFAULT_COORDINATES;
-                    position[0] = 100f64;
-                    position[1] = -100f64;
-                    position
-                },
-            };
-            pop[2] = Particle {
-                mass: 10f64,
-                speed: DEFAULT_COORDINATES,
-                position: {
-                    let mut position = DEFAULT_COORDINATES;
-                    position[0] = -100f64;
-                    position[1] = -100f64;
-                    position
-                },
-            }
-        }
-        pop
-    }
-}
-
 #[inline(always)]
 pub fn distance_squared(Coordinates(a): Coordinates, Coordinates(b): Coordinates) -> f64 {
     distance_squared_macro!(a, b)
 }
 
-pub fn apply_force(population: &mut Population) {
-    let previous_population = *population;
+/// Add to each of `speeds` the speed gained during `dt` by the particle of `population` with the same index, and
+/// return the pairs of particles close enough to be merged
+pub fn accelerate(
+    population: &Population,
+    gravity: &Gravity,
+    dt: f64,
+    speeds: &mut [Coordinates],
+) -> Vec<(usize, usize)> {
+    match gravity.solver {
+        Solver::BruteForce => accelerate_brute_force(population, gravity, dt, speeds),
+        Solver::BarnesHut { theta } => barnes_hut::accelerate(population, gravity, theta, dt, speeds),
+    }
+}
+
+fn accelerate_brute_force(
+    population: &Population,
+    gravity: &Gravity,
+    dt: f64,
+    speeds: &mut [Coordinates],
+) -> Vec<(usize, usize)> {
+    let g = gravity.g * dt;
+    let minimal_distance_squared = gravity.minimal_distance * gravity.minimal_distance;
 
     // This vector will contain the pairs of particles index to merge together.
     let mut to_merge = Vec::new();
     let to_merge_mutex: Mutex<&mut Vec<(usize, usize)>> = Mutex::new(&mut to_merge);
 
-    population
+    speeds
         .par_iter_mut()
-        .zip(0..POP_SIZE)
-        .for_each(|(computed_particle_a, particle_a_index)| {
-            let particle_a = &previous_population[particle_a_index];
+        .enumerate()
+        .for_each(|(particle_a_index, speed_a)| {
+            let particle_a = &population[particle_a_index];
 
             if particle_a.mass != 0f64 {
-                for (particle_b_index, particle_b) in previous_population.iter().enumerate() {
+                for (particle_b_index, particle_b) in population.iter().enumerate() {
                     // If a particle has no mass it is exactly like it does not exist
                     if particle_b.mass == 0f64 || particle_a_index == particle_b_index {
                         continue;
@@ -172,17 +135,17 @@ pub fn apply_force(population: &mut Population) {
 
                     // These variables may seem esoteric, but they were set up because benchmarks showed that
                     // they provided better performances than more natural choices
-                    let g_by_d_squared = G / (distance_squared);
+                    let g_by_d_squared = g / (distance_squared);
                     let inverse_distance_square_root = 1f64 / distance_squared.sqrt();
                     let force_by_mass_a = particle_b.mass * g_by_d_squared * inverse_distance_square_root;
 
                     // Accelerate the two particles in all dimensions
                     for i in 0..DIMENSIONS {
                         let direction = particle_b.position[i] - particle_a.position[i];
-                        computed_particle_a.speed[i] += direction * force_by_mass_a;
+                        speed_a[i] += direction * force_by_mass_a;
                     }
 
-                    if distance_squared < MINIMAL_DISTANCE_SQUARED {
+                    if distance_squared < minimal_distance_squared {
                         let mut to_merge = to_merge_mutex
                             .lock()
                             .expect("Critical unrecoverable failure when registering particles to merge");
@@ -190,12 +153,12 @@ pub fn apply_force(population: &mut Population) {
                     }
                 }
             }
-            // Move particle based on its speed during the previous frame
-            for i in 0..DIMENSIONS {
-                computed_particle_a.position[i] += particle_a.speed[i];
-            }
         });
 
+    to_merge
+}
+
+fn merge_particles(population: &mut Population, to_merge: &[(usize, usize)]) {
     for (particle_a_index, particle_b_index) in to_merge.iter() {
         let particle_a = population[*particle_a_index];
         let particle_b = population[*particle_b_index];
@@ -220,34 +183,62 @@ pub fn apply_force(population: &mut Population) {
     }
 }
 
+fn remove_massless_particles(population: &mut Population) -> Vec<usize> {
+    let removed: Vec<usize> = population
+        .iter()
+        .enumerate()
+        .filter(|(_, particle)| particle.mass == 0f64)
+        .map(|(index, _)| index)
+        .collect();
+    if !removed.is_empty() {
+        population.retain(|particle| particle.mass != 0f64);
+    }
+    removed
+}
+
 #[cfg(test)]
 pub mod test {
-    use crate::physics::{Coordinates, DEFAULT_POP, Particle, apply_force};
+    use crate::physics::integrators::Euler;
+    use crate::physics::{Coordinates, Gravity, Particle, Simulation, Solver};
+
+    /// Coordinates starting with `components`, the others being 0, for tests to hold whatever the number of dimensions
+    pub fn coordinates<const N: usize>(components: [f64; N]) -> Coordinates {
+        Coordinates::new(std::array::from_fn(|i| components.get(i).copied().unwrap_or(0f64)))
+    }
 
     #[test]
     fn test_apply_force() {
-        let mut population = DEFAULT_POP;
+        let mut population = vec![Particle::default(); 3];
         population[0] = Particle {
             mass: 3f64,
-            speed: Coordinates([0f64, 0f64]),
-            position: Coordinates([10f64, 10f64]),
+            speed: coordinates([0f64, 0f64]),
+            position: coordinates([10f64, 10f64]),
         };
         population[1] = Particle {
             mass: 2f64,
-            speed: Coordinates([0f64, 0f64]),
-            position: Coordinates([-10f64, -10f64]),
+            speed: coordinates([0f64, 0f64]),
+            position: coordinates([-10f64, -10f64]),
         };
         population[2] = Particle {
             mass: 1f64,
-            speed: Coordinates([0f64, 0f64]),
-            position: Coordinates([10f64, -10f64]),
+            speed: coordinates([0f64, 0f64]),
+            position: coordinates([10f64, -10f64]),
+        };
+        let mut simulation = Simulation {
+            gravity: Gravity {
+                g: 1f64,
+                minimal_distance: 0f64,
+                solver: Solver::BruteForce,
+            },
+            integrator: Box::new(Euler),
+            dt: 1f64,
         };
         for _ in 0..100 {
-            apply_force(&mut population);
+            simulation.step(&mut population);
         }
         assert_eq!(
             population[0].position,
-            Coordinates::new([-2.6124097114690477, -41.87865599101741])
+            coordinates([-2.6124097114690477, -41.87865599101741])
         )
     }
 }
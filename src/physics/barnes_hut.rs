@@ -0,0 +1,314 @@
+// Barnes-Hut approximation of the gravitational forces. Particles are sorted into a tree where each node splits its
+// region of space in 2^DIMENSIONS children, and groups of particles far enough from the particle being accelerated
+// are treated as a single particle located at their centre of mass.
+
+use crate::physics::{Coordinates, DEFAULT_COORDINATES, DIMENSIONS, Gravity, Population, distance_squared};
+use rayon::iter::IndexedParallelIterator;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use std::sync::Mutex;
+
+const CHILDREN_PER_NODE: usize = 1 << DIMENSIONS;
+
+// Particles sharing the exact same position can never be separated by splitting space, so past this depth they are
+// kept together in the same leaf
+const MAX_DEPTH: usize = 64;
+
+struct Node {
+    center: Coordinates,
+    half_size: f64,
+    mass: f64,
+    center_of_mass: Coordinates,
+    // Children are stored contiguously, so only the index of the first one is needed
+    first_child: Option<usize>,
+    particles: Vec<usize>,
+}
+
+impl Node {
+    fn new(center: Coordinates, half_size: f64) -> Self {
+        Node {
+            center,
+            half_size,
+            mass: 0f64,
+            center_of_mass: DEFAULT_COORDINATES,
+            first_child: None,
+            particles: Vec::new(),
+        }
+    }
+
+    fn add_mass(&mut self, mass: f64, position: Coordinates) {
+        let total_mass = self.mass + mass;
+        for i in 0..DIMENSIONS {
+            self.center_of_mass[i] = (self.center_of_mass[i] * self.mass + position[i] * mass) / total_mass;
+        }
+        self.mass = total_mass;
+    }
+
+    fn contains(&self, position: Coordinates) -> bool {
+        (0..DIMENSIONS).all(|i| (position[i] - self.center[i]).abs() <= self.half_size)
+    }
+
+    fn child_offset(&self, position: Coordinates) -> usize {
+        (0..DIMENSIONS)
+            .filter(|&i| position[i] >= self.center[i])
+            .fold(0, |offset, i| offset | (1 << i))
+    }
+
+    fn child_center(&self, offset: usize) -> Coordinates {
+        let mut center = self.center;
+        for i in 0..DIMENSIONS {
+            if offset & (1 << i) == 0 {
+                center[i] -= self.half_size / 2f64;
+            } else {
+                center[i] += self.half_size / 2f64;
+            }
+        }
+        center
+    }
+}
+
+// Settings of a step shared by the accelerations of every particle
+struct StepParameters {
+    theta_squared: f64,
+    // Gravitational constant multiplied by the time step
+    g: f64,
+    minimal_distance_squared: f64,
+}
+
+pub struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    pub fn new(population: &Population) -> Self {
+        let mut min = [f64::INFINITY; DIMENSIONS];
+        let mut max = [f64::NEG_INFINITY; DIMENSIONS];
+        for particle in population.iter().filter(|particle| particle.mass != 0f64) {
+            for i in 0..DIMENSIONS {
+                min[i] = min[i].min(particle.position[i]);
+                max[i] = max[i].max(particle.position[i]);
+            }
+        }
+
+        let mut tree = Tree { nodes: Vec::new() };
+        if min[0] > max[0] {
+            // No particle has a mass, the tree stays empty
+            return tree;
+        }
+
+        let mut center = DEFAULT_COORDINATES;
+        let mut half_size = 0f64;
+        for i in 0..DIMENSIONS {
+            center[i] = (min[i] + max[i]) / 2f64;
+            half_size = half_size.max((max[i] - min[i]) / 2f64);
+        }
+        tree.nodes.push(Node::new(center, half_size));
+
+        for (particle_index, particle) in population.iter().enumerate() {
+            if particle.mass != 0f64 {
+                tree.insert(population, particle_index);
+            }
+        }
+        tree
+    }
+
+    fn insert(&mut self, population: &Population, particle_index: usize) {
+        let particle = &population[particle_index];
+        let mut node_index = 0;
+        let mut depth = 0;
+        loop {
+            self.nodes[node_index].add_mass(particle.mass, particle.position);
+            let first_child = match self.nodes[node_index].first_child {
+                Some(first_child) => first_child,
+                None if self.nodes[node_index].particles.is_empty() || depth == MAX_DEPTH => {
+                    self.nodes[node_index].particles.push(particle_index);
+                    return;
+                }
+                None => self.split(population, node_index),
+            };
+            node_index = first_child + self.nodes[node_index].child_offset(particle.position);
+            depth += 1;
+        }
+    }
+
+    // Turn a leaf into an internal node by moving its particles into newly created children
+    fn split(&mut self, population: &Population, node_index: usize) -> usize {
+        let first_child = self.nodes.len();
+        for offset in 0..CHILDREN_PER_NODE {
+            let node = &self.nodes[node_index];
+            self.nodes
+                .push(Node::new(node.child_center(offset), node.half_size / 2f64));
+        }
+        self.nodes[node_index].first_child = Some(first_child);
+        for particle_index in std::mem::take(&mut self.nodes[node_index].particles) {
+            let particle = &population[particle_index];
+            let child_index = first_child + self.nodes[node_index].child_offset(particle.position);
+            let child = &mut self.nodes[child_index];
+            child.add_mass(particle.mass, particle.position);
+            child.particles.push(particle_index);
+        }
+        first_child
+    }
+
+    // Add to `speed` the speed gained by the particle at `particle_index`
+    fn accelerate(
+        &self,
+        population: &Population,
+        particle_index: usize,
+        parameters: &StepParameters,
+        speed: &mut Coordinates,
+        to_merge: &mut Vec<(usize, usize)>,
+    ) {
+        let particle_a = &population[particle_index];
+        let mut to_visit = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(node_index) = to_visit.pop() {
+            let node = &self.nodes[node_index];
+            match node.first_child {
+                None => {
+                    for &particle_b_index in node.particles.iter() {
+                        if particle_b_index == particle_index {
+                            continue;
+                        }
+                        let particle_b = &population[particle_b_index];
+                        let distance_squared = distance_squared(particle_a.position, particle_b.position);
+                        pull(
+                            speed,
+                            particle_a.position,
+                            particle_b.position,
+                            particle_b.mass,
+                            distance_squared,
+                            parameters.g,
+                        );
+                        if distance_squared < parameters.minimal_distance_squared {
+                            to_merge.push((particle_index, particle_b_index));
+                        }
+                    }
+                }
+                Some(first_child) => {
+                    let distance_squared = distance_squared(particle_a.position, node.center_of_mass);
+                    let size = 2f64 * node.half_size;
+                    // A node holding the particle itself is always opened, as its centre of mass would include the
+                    // particle and make it pull on itself
+                    if !node.contains(particle_a.position) && size * size < parameters.theta_squared * distance_squared
+                    {
+                        pull(
+                            speed,
+                            particle_a.position,
+                            node.center_of_mass,
+                            node.mass,
+                            distance_squared,
+                            parameters.g,
+                        );
+                    } else {
+                        to_visit.extend(first_child..first_child + CHILDREN_PER_NODE);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn pull(speed: &mut Coordinates, from: Coordinates, to: Coordinates, mass: f64, distance_squared: f64, g: f64) {
+    // Same formulation as the brute force solver, see `accelerate_brute_force`
+    let g_by_d_squared = g / (distance_squared);
+    let inverse_distance_square_root = 1f64 / distance_squared.sqrt();
+    let force_by_mass = mass * g_by_d_squared * inverse_distance_square_root;
+    for i in 0..DIMENSIONS {
+        speed[i] += (to[i] - from[i]) * force_by_mass;
+    }
+}
+
+pub fn accelerate(
+    population: &Population,
+    gravity: &Gravity,
+    theta: f64,
+    dt: f64,
+    speeds: &mut [Coordinates],
+) -> Vec<(usize, usize)> {
+    let tree = Tree::new(population);
+    let parameters = StepParameters {
+        theta_squared: theta * theta,
+        g: gravity.g * dt,
+        minimal_distance_squared: gravity.minimal_distance * gravity.minimal_distance,
+    };
+
+    // This vector will contain the pairs of particles index to merge together.
+    let mut to_merge = Vec::new();
+    let to_merge_mutex: Mutex<&mut Vec<(usize, usize)>> = Mutex::new(&mut to_merge);
+
+    speeds
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(particle_a_index, speed_a)| {
+            if population[particle_a_index].mass != 0f64 {
+                let mut particle_a_to_merge = Vec::new();
+                tree.accelerate(
+                    population,
+                    particle_a_index,
+                    &parameters,
+                    speed_a,
+                    &mut particle_a_to_merge,
+                );
+                if !particle_a_to_merge.is_empty() {
+                    to_merge_mutex
+                        .lock()
+                        .expect("Critical unrecoverable failure when registering particles to merge")
+                        .extend(particle_a_to_merge);
+                }
+            }
+        });
+
+    to_merge
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::physics::test::coordinates;
+    use crate::physics::{DEFAULT_COORDINATES, Gravity, Particle, Solver, accelerate, distance_squared};
+    use crate::random::SplitMix64;
+    use crate::scenario;
+
+    #[test]
+    fn test_barnes_hut_matches_brute_force() {
+        let population = scenario::random_in_screen(1000, 2560, 1440, 10f64, &mut SplitMix64::new(0));
+        let mut gravity = Gravity {
+            g: 1f64,
+            minimal_distance: 0f64,
+            solver: Solver::BruteForce,
+        };
+
+        let mut brute_force_speeds = vec![DEFAULT_COORDINATES; population.len()];
+        accelerate(&population, &gravity, 1f64, &mut brute_force_speeds);
+        gravity.solver = Solver::BarnesHut { theta: 0.5 };
+        let mut barnes_hut_speeds = vec![DEFAULT_COORDINATES; population.len()];
+        accelerate(&population, &gravity, 1f64, &mut barnes_hut_speeds);
+
+        let mut error_squared = 0f64;
+        let mut norm_squared = 0f64;
+        for (expected, actual) in brute_force_speeds.iter().zip(barnes_hut_speeds.iter()) {
+            error_squared += distance_squared(*expected, *actual);
+            norm_squared += distance_squared(*expected, DEFAULT_COORDINATES);
+        }
+        let relative_error = (error_squared / norm_squared).sqrt();
+        assert!(relative_error < 0.01, "Relative error too high: {relative_error}");
+
+        // With a wide opening angle the root would be approximated for every particle, which includes its own mass
+        let particle = |x| Particle {
+            mass: 1f64,
+            speed: DEFAULT_COORDINATES,
+            position: coordinates([x, 0f64]),
+        };
+        let population = vec![particle(0f64), particle(1f64), particle(1000f64)];
+        gravity.solver = Solver::BruteForce;
+        let mut brute_force_speeds = vec![DEFAULT_COORDINATES; population.len()];
+        accelerate(&population, &gravity, 1f64, &mut brute_force_speeds);
+        gravity.solver = Solver::BarnesHut { theta: 10f64 };
+        let mut barnes_hut_speeds = vec![DEFAULT_COORDINATES; population.len()];
+        accelerate(&population, &gravity, 1f64, &mut barnes_hut_speeds);
+        // The two close particles only see each other and the lone far one, so the forces they get are exact
+        for particle_index in 0..2 {
+            let error = distance_squared(brute_force_speeds[particle_index], barnes_hut_speeds[particle_index]);
+            assert!(error.sqrt() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+// Responsible for measuring the conserved quantities of a population, to check whether a simulation stays
+// physically sound
+
+use crate::physics::{Coordinates, DEFAULT_COORDINATES, DIMENSIONS, Gravity, Population, distance_squared};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use std::fmt::{Display, Formatter};
+
+/// Number of independent planes of rotation, hence of angular momentum components, in DIMENSIONS dimensions
+pub const PLANES: usize = DIMENSIONS * (DIMENSIONS - 1) / 2;
+
+/// Plane `(i, j)` with `i < j` of each angular momentum component, in the order they are stored
+pub fn planes() -> impl Iterator<Item = (usize, usize)> {
+    (0..DIMENSIONS).flat_map(|i| (i + 1..DIMENSIONS).map(move |j| (i, j)))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Diagnostics {
+    pub mass: f64,
+    pub kinetic_energy: f64,
+    pub potential_energy: f64,
+    pub momentum: Coordinates,
+    /// Bivector components of the angular momentum around the origin, see `planes`
+    pub angular_momentum: [f64; PLANES],
+    pub center_of_mass: Coordinates,
+}
+
+impl Diagnostics {
+    pub fn new(population: &Population, gravity: &Gravity) -> Self {
+        let mut diagnostics = Diagnostics {
+            mass: 0f64,
+            kinetic_energy: 0f64,
+            potential_energy: potential_energy(population, gravity),
+            momentum: DEFAULT_COORDINATES,
+            angular_momentum: [0f64; PLANES],
+            center_of_mass: DEFAULT_COORDINATES,
+        };
+        for particle in population.iter().filter(|particle| particle.mass != 0f64) {
+            diagnostics.mass += particle.mass;
+            diagnostics.kinetic_energy += particle.mass * distance_squared(particle.speed, DEFAULT_COORDINATES) / 2f64;
+            for i in 0..DIMENSIONS {
+                diagnostics.momentum[i] += particle.mass * particle.speed[i];
+                diagnostics.center_of_mass[i] += particle.mass * particle.position[i];
+            }
+            for (component, (i, j)) in diagnostics.angular_momentum.iter_mut().zip(planes()) {
+                *component += particle.mass
+                    * (particle.position[i] * particle.speed[j] - particle.position[j] * particle.speed[i]);
+            }
+        }
+        if diagnostics.mass != 0f64 {
+            for i in 0..DIMENSIONS {
+                diagnostics.center_of_mass[i] /= diagnostics.mass;
+            }
+        }
+        diagnostics
+    }
+
+    pub fn total_energy(&self) -> f64 {
+        self.kinetic_energy + self.potential_energy
+    }
+}
+
+// Particles closer than the minimal distance are about to be merged, so pairs are never considered closer than that
+fn potential_energy(population: &Population, gravity: &Gravity) -> f64 {
+    population
+        .par_iter()
+        .enumerate()
+        .filter(|(_, particle_a)| particle_a.mass != 0f64)
+        .map(|(particle_a_index, particle_a)| {
+            population[particle_a_index + 1..]
+                .iter()
+                .filter(|particle_b| particle_b.mass != 0f64)
+                .map(|particle_b| {
+                    let distance = distance_squared(particle_a.position, particle_b.position)
+                        .sqrt()
+                        .max(gravity.minimal_distance);
+                    -gravity.g * particle_a.mass * particle_b.mass / distance
+                })
+                .sum::<f64>()
+        })
+        .sum()
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Mass: {}", self.mass)?;
+        writeln!(f, "Kinetic energy: {}", self.kinetic_energy)?;
+        writeln!(f, "Potential energy: {}", self.potential_energy)?;
+        writeln!(f, "Total energy: {}", self.total_energy())?;
+        writeln!(f, "Momentum: {:?}", self.momentum.0)?;
+        write!(f, "Angular momentum:")?;
+        for (component, (i, j)) in self.angular_momentum.iter().zip(planes()) {
+            write!(f, " {i}{j}={component}")?;
+        }
+        writeln!(f)?;
+        write!(f, "Center of mass: {:?}", self.center_of_mass.0)
+    }
+}
+
+/// Diagnostics sampled every `interval` steps of a run, a zero `interval` disabling them
+pub struct DiagnosticsLog {
+    interval: u64,
+    gravity: Gravity,
+    first: Option<(u64, Diagnostics)>,
+    last: Option<(u64, Diagnostics)>,
+    max_energy_drift: f64,
+}
+
+impl DiagnosticsLog {
+    pub fn new(interval: u64, gravity: Gravity) -> Self {
+        DiagnosticsLog {
+            interval,
+            gravity,
+            first: None,
+            last: None,
+            max_energy_drift: 0f64,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.interval != 0
+    }
+
+    pub fn record(&mut self, step: u64, population: &Population) {
+        if self.interval == 0 || !step.is_multiple_of(self.interval) {
+            return;
+        }
+        let diagnostics = Diagnostics::new(population, &self.gravity);
+        match self.first {
+            None => self.first = Some((step, diagnostics)),
+            Some((_, first)) => {
+                let drift = ((diagnostics.total_energy() - first.total_energy()) / first.total_energy()).abs();
+                self.max_energy_drift = self.max_energy_drift.max(drift);
+            }
+        }
+        self.last = Some((step, diagnostics));
+    }
+}
+
+impl Display for DiagnosticsLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let (Some((first_step, first)), Some((last_step, last))) = (self.first, self.last) {
+            writeln!(f, "Diagnostics at step {first_step}:\n{first}")?;
+            writeln!(f, "Diagnostics at step {last_step}:\n{last}")?;
+            write!(f, "Maximal relative energy drift: {}", self.max_energy_drift)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::physics::diagnostics::{Diagnostics, PLANES};
+    use crate::physics::test::coordinates;
+    use crate::physics::{Gravity, Particle, Solver};
+
+    #[test]
+    fn test_diagnostics() {
+        let population = vec![
+            Particle {
+                mass: 1f64,
+                speed: coordinates([0f64, 1f64]),
+                position: coordinates([1f64, 0f64]),
+            },
+            Particle {
+                mass: 1f64,
+                speed: coordinates([0f64, -1f64]),
+                position: coordinates([-1f64, 0f64]),
+            },
+        ];
+        let gravity = Gravity {
+            g: 1f64,
+            minimal_distance: 0f64,
+            solver: Solver::BruteForce,
+        };
+        let diagnostics = Diagnostics::new(&population, &gravity);
+        assert_eq!(diagnostics.mass, 2f64);
+        assert_eq!(diagnostics.kinetic_energy, 1f64);
+        assert_eq!(diagnostics.potential_energy, -0.5f64);
+        assert_eq!(diagnostics.momentum, coordinates([0f64, 0f64]));
+        // Within the plane of the first two axes only
+        let mut angular_momentum = [0f64; PLANES];
+        angular_momentum[0] = 2f64;
+        assert_eq!(diagnostics.angular_momentum, angular_momentum);
+        assert_eq!(diagnostics.center_of_mass, coordinates([0f64, 0f64]));
+    }
+}
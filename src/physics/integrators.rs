@@ -0,0 +1,235 @@
+// Responsible for moving particles through time using the speeds gained from the gravitational forces
+
+use crate::physics::{Coordinates, DEFAULT_COORDINATES, DIMENSIONS, Gravity, Population, accelerate};
+
+pub trait Integrator: Send {
+    /// Advance `population` by `dt` and return the pairs of particles close enough to be merged
+    fn step(&mut self, population: &mut Population, gravity: &Gravity, dt: f64) -> Vec<(usize, usize)>;
+}
+
+/// Build the integrator named `name`, if it is one of `euler`, `leapfrog`, `velocity-verlet` or `rk4`
+pub fn from_name(name: &str) -> Option<Box<dyn Integrator>> {
+    match name {
+        "euler" => Some(Box::new(Euler)),
+        "leapfrog" => Some(Box::new(Leapfrog::default())),
+        "velocity-verlet" => Some(Box::new(VelocityVerlet::default())),
+        "rk4" => Some(Box::new(RungeKutta4)),
+        _ => None,
+    }
+}
+
+fn compute_accelerations(population: &Population, gravity: &Gravity) -> (Vec<Coordinates>, Vec<(usize, usize)>) {
+    let mut accelerations = vec![DEFAULT_COORDINATES; population.len()];
+    let to_merge = accelerate(population, gravity, 1f64, &mut accelerations);
+    (accelerations, to_merge)
+}
+
+// Accelerations computed at the end of a step are still valid at the beginning of the next one, unless some particles
+// were merged in between
+#[derive(Default)]
+struct AccelerationsCache(Option<Vec<Coordinates>>);
+
+impl AccelerationsCache {
+    fn take_or_compute(&mut self, population: &Population, gravity: &Gravity) -> Vec<Coordinates> {
+        match self.0.take() {
+            Some(accelerations) if accelerations.len() == population.len() => accelerations,
+            _ => compute_accelerations(population, gravity).0,
+        }
+    }
+
+    fn store(&mut self, accelerations: Vec<Coordinates>, to_merge: &[(usize, usize)]) {
+        self.0 = if to_merge.is_empty() { Some(accelerations) } else { None };
+    }
+}
+
+/// Explicit Euler: particles move according to the speed they had at the beginning of the step. With a `dt` of 1 this
+/// is the historical behaviour of rengine.
+pub struct Euler;
+
+impl Integrator for Euler {
+    fn step(&mut self, population: &mut Population, gravity: &Gravity, dt: f64) -> Vec<(usize, usize)> {
+        let mut speeds: Vec<Coordinates> = population.iter().map(|particle| particle.speed).collect();
+        let to_merge = accelerate(population, gravity, dt, &mut speeds);
+        for (particle, speed) in population.iter_mut().zip(speeds) {
+            for i in 0..DIMENSIONS {
+                particle.position[i] += particle.speed[i] * dt;
+            }
+            particle.speed = speed;
+        }
+        to_merge
+    }
+}
+
+/// Leapfrog in its kick-drift-kick form: half a kick, a full drift, then half a kick with the new accelerations
+#[derive(Default)]
+pub struct Leapfrog {
+    accelerations: AccelerationsCache,
+}
+
+impl Integrator for Leapfrog {
+    fn step(&mut self, population: &mut Population, gravity: &Gravity, dt: f64) -> Vec<(usize, usize)> {
+        let accelerations = self.accelerations.take_or_compute(population, gravity);
+        for (particle, acceleration) in population.iter_mut().zip(accelerations) {
+            for i in 0..DIMENSIONS {
+                particle.speed[i] += acceleration[i] * dt / 2f64;
+                particle.position[i] += particle.speed[i] * dt;
+            }
+        }
+        let (accelerations, to_merge) = compute_accelerations(population, gravity);
+        for (particle, acceleration) in population.iter_mut().zip(accelerations.iter()) {
+            for i in 0..DIMENSIONS {
+                particle.speed[i] += acceleration[i] * dt / 2f64;
+            }
+        }
+        self.accelerations.store(accelerations, &to_merge);
+        to_merge
+    }
+}
+
+/// Velocity Verlet: positions follow a second order expansion, speeds the average of the old and new accelerations
+#[derive(Default)]
+pub struct VelocityVerlet {
+    accelerations: AccelerationsCache,
+}
+
+impl Integrator for VelocityVerlet {
+    fn step(&mut self, population: &mut Population, gravity: &Gravity, dt: f64) -> Vec<(usize, usize)> {
+        let previous_accelerations = self.accelerations.take_or_compute(population, gravity);
+        for (particle, acceleration) in population.iter_mut().zip(previous_accelerations.iter()) {
+            for i in 0..DIMENSIONS {
+                particle.position[i] += particle.speed[i] * dt + acceleration[i] * dt * dt / 2f64;
+            }
+        }
+        let (accelerations, to_merge) = compute_accelerations(population, gravity);
+        for ((particle, previous_acceleration), acceleration) in population
+            .iter_mut()
+            .zip(previous_accelerations)
+            .zip(accelerations.iter())
+        {
+            for i in 0..DIMENSIONS {
+                particle.speed[i] += (previous_acceleration[i] + acceleration[i]) * dt / 2f64;
+            }
+        }
+        self.accelerations.store(accelerations, &to_merge);
+        to_merge
+    }
+}
+
+/// Classic fourth order Runge-Kutta. Not symplectic, but very accurate for small time steps at the cost of four force
+/// computations per step.
+pub struct RungeKutta4;
+
+// Return `a + b * h` for each pair of coordinates
+fn add_scaled(a: &[Coordinates], b: &[Coordinates], h: f64) -> Vec<Coordinates> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let mut sum = *a;
+            for i in 0..DIMENSIONS {
+                sum[i] += b[i] * h;
+            }
+            sum
+        })
+        .collect()
+}
+
+fn moved(population: &Population, speeds: &[Coordinates], dt: f64) -> Population {
+    population
+        .iter()
+        .zip(speeds)
+        .map(|(particle, speed)| {
+            let mut particle = *particle;
+            for i in 0..DIMENSIONS {
+                particle.position[i] += speed[i] * dt;
+            }
+            particle
+        })
+        .collect()
+}
+
+impl Integrator for RungeKutta4 {
+    fn step(&mut self, population: &mut Population, gravity: &Gravity, dt: f64) -> Vec<(usize, usize)> {
+        let speeds_1: Vec<Coordinates> = population.iter().map(|particle| particle.speed).collect();
+        let (accelerations_1, to_merge) = compute_accelerations(population, gravity);
+
+        let speeds_2 = add_scaled(&speeds_1, &accelerations_1, dt / 2f64);
+        let (accelerations_2, _) = compute_accelerations(&moved(population, &speeds_1, dt / 2f64), gravity);
+
+        let speeds_3 = add_scaled(&speeds_1, &accelerations_2, dt / 2f64);
+        let (accelerations_3, _) = compute_accelerations(&moved(population, &speeds_2, dt / 2f64), gravity);
+
+        let speeds_4 = add_scaled(&speeds_1, &accelerations_3, dt);
+        let (accelerations_4, _) = compute_accelerations(&moved(population, &speeds_3, dt), gravity);
+
+        for (index, particle) in population.iter_mut().enumerate() {
+            for i in 0..DIMENSIONS {
+                particle.position[i] +=
+                    (speeds_1[index][i] + 2f64 * speeds_2[index][i] + 2f64 * speeds_3[index][i] + speeds_4[index][i])
+                        * dt
+                        / 6f64;
+                particle.speed[i] += (accelerations_1[index][i]
+                    + 2f64 * accelerations_2[index][i]
+                    + 2f64 * accelerations_3[index][i]
+                    + accelerations_4[index][i])
+                    * dt
+                    / 6f64;
+            }
+        }
+        to_merge
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::physics::diagnostics::Diagnostics;
+    use crate::physics::integrators::from_name;
+    use crate::physics::test::coordinates;
+    use crate::physics::{Gravity, Particle, Population, Solver, distance_squared};
+    use std::f64::consts::TAU;
+
+    #[test]
+    fn test_integrators_on_circular_orbit() {
+        let gravity = Gravity {
+            g: 1f64,
+            minimal_distance: 0f64,
+            solver: Solver::BruteForce,
+        };
+        // Two particles of mass 1 at distance 2, each on a circular orbit of radius 1 around their centre of mass
+        let particle = |side: f64| Particle {
+            mass: 1f64,
+            speed: coordinates([0f64, side * 0.5]),
+            position: coordinates([side, 0f64]),
+        };
+        let initial_population: Population = vec![particle(1f64), particle(-1f64)];
+        let energy = |population: &Population| {
+            let diagnostics = Diagnostics::new(population, &gravity);
+            diagnostics.kinetic_energy + diagnostics.potential_energy
+        };
+        let initial_energy = energy(&initial_population);
+
+        let dt = 0.01;
+        let periods = 5;
+        let steps = (periods as f64 * 2f64 * TAU / dt).round() as usize;
+        // Largest relative energy error along the run, and distance to the starting point after whole periods
+        let run = |name| {
+            let mut integrator = from_name(name).unwrap();
+            let mut population = initial_population.clone();
+            let mut energy_error = 0f64;
+            for _ in 0..steps {
+                assert!(integrator.step(&mut population, &gravity, dt).is_empty());
+                energy_error = energy_error.max(((energy(&population) - initial_energy) / initial_energy).abs());
+            }
+            let position_error = distance_squared(population[0].position, initial_population[0].position).sqrt();
+            (energy_error, position_error)
+        };
+
+        let (euler_energy_error, euler_position_error) = run("euler");
+        for name in ["leapfrog", "velocity-verlet", "rk4"] {
+            let (energy_error, position_error) = run(name);
+            assert!(energy_error < 1e-6, "{name} energy error: {energy_error}");
+            assert!(position_error < 1e-2, "{name} position error: {position_error}");
+            assert!(energy_error * 100f64 < euler_energy_error);
+            assert!(position_error * 10f64 < euler_position_error);
+        }
+    }
+}
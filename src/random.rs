@@ -0,0 +1,59 @@
+// Responsible for the random numbers of a run, which all come from a generator seeded by the SEED setting. The seed is
+// printed at startup, so that any run can be replayed by giving it back to the same build.
+
+use rand::RngCore;
+use rand::rand_core::impls;
+
+/// SplitMix64 generator, see https://prng.di.unimi.it/splitmix64.c. Its raw output only depends on the seed, but
+/// floats and ranges are sampled from it by the rand crate, so runs only replay exactly for a given rand version.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+}
+
+impl RngCore for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dst)
+    }
+}
+
+/// Seed of runs which are not given one
+pub fn new_seed() -> u64 {
+    rand::random()
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::random::SplitMix64;
+    use rand::{Rng, RngCore};
+
+    #[test]
+    fn test_split_mix_64() {
+        // Reference outputs of splitmix64.c
+        let mut rng = SplitMix64::new(1234567);
+        assert_eq!(rng.next_u64(), 6457827717110365317);
+        assert_eq!(rng.next_u64(), 3203168211198807973);
+
+        let draw = |seed| SplitMix64::new(seed).random_range(0f64..1f64);
+        assert_eq!(draw(42), draw(42));
+        assert_ne!(draw(42), draw(43));
+    }
+}
@@ -1,21 +1,34 @@
-use crate::physics::{Population, apply_force};
-use proc_macros::get_iterations_from_env_var;
+use crate::config::Config;
+use crate::physics::diagnostics::DiagnosticsLog;
+use crate::physics::{Population, Simulation};
+use crate::snapshot;
 use std::time::Instant;
 
-const ITERATIONS: u32 = get_iterations_from_env_var!();
-
-pub fn run(population: Population) {
+pub fn run(population: Population, simulation: &mut Simulation, config: &Config, first_step: u64) {
+    let iterations = config.iterations;
     let mut population = population;
+    let mut diagnostics = DiagnosticsLog::new(config.diagnostics_interval, simulation.gravity);
+    diagnostics.record(first_step, &population);
     let start = Instant::now();
-    for _ in 0..ITERATIONS {
-        apply_force(&mut population);
+    for iteration in 1..=iterations {
+        simulation.step(&mut population);
+        diagnostics.record(first_step + iteration as u64, &population);
     }
     let duration = start.elapsed();
     println!("Total time elapsed is: {:?}", duration);
-    if ITERATIONS > 0 {
-        println!("Microsec per update: {:?}", duration.as_micros() / ITERATIONS as u128);
+    if iterations > 0 {
+        println!("Microsec per update: {:?}", duration.as_micros() / iterations as u128);
     }
     if duration.as_millis() > 0 {
-        println!("UPS: {:?}", (ITERATIONS * 1000) / duration.as_millis() as u32);
+        println!("UPS: {:?}", (iterations * 1000) / duration.as_millis() as u32);
+    }
+    if diagnostics.is_enabled() {
+        println!("{diagnostics}");
     }
+    snapshot::save_if_requested(
+        config.save_snapshot.as_deref(),
+        &simulation.gravity,
+        &population,
+        first_step + iterations as u64,
+    );
 }
@@ -0,0 +1,299 @@
+// Responsible for defining the surfaces particles can be drawn on
+
+use crate::font;
+use crate::font::{ADVANCE, GLYPH_WIDTH, LINE_HEIGHT};
+use crate::input::InputEvent;
+
+pub const BYTES_PER_PIXEL: usize = 4;
+
+/// Red, green, blue and alpha components of a pixel
+pub type Color = [u8; BYTES_PER_PIXEL];
+
+/// Surface made of a back buffer that is drawn into, and presented at once by `draw`
+pub trait RenderTarget {
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    fn clear(&mut self);
+
+    /// Dim everything drawn so far, each color channel keeping `decay` of its value, to leave fading trails behind
+    /// moving particles. Surfaces which cannot read their pixels back simply clear.
+    fn fade(&mut self, decay: f64) {
+        let _ = decay;
+        self.clear();
+    }
+
+    /// Pixels outside of the surface are ignored
+    fn draw_pixel(&mut self, x: isize, y: isize, color: &Color);
+
+    /// Only the part within the surface is visited, however large the square
+    fn draw_square(&mut self, x: isize, y: isize, width: usize, height: usize, color: &Color) {
+        let x_end = x
+            .saturating_add(width.min(isize::MAX as usize) as isize)
+            .min(self.width() as isize);
+        let y_end = y
+            .saturating_add(height.min(isize::MAX as usize) as isize)
+            .min(self.height() as isize);
+        for i in x.max(0)..x_end {
+            for j in y.max(0)..y_end {
+                self.draw_pixel(i, j, color);
+            }
+        }
+    }
+
+    /// Only the part within the surface is visited, however large the circle
+    fn draw_circle(&mut self, x: isize, y: isize, radius: usize, color: &Color) {
+        // Small enough for its square not to overflow
+        let radius = radius.min(i32::MAX as usize) as isize;
+        let rsqr = radius * radius;
+        let dx_range =
+            0isize.saturating_sub(x).max(-radius)..=(self.width() as isize - 1).saturating_sub(x).min(radius);
+        for dx in dx_range {
+            let dy_range =
+                0isize.saturating_sub(y).max(-radius)..=(self.height() as isize - 1).saturating_sub(y).min(radius);
+            for dy in dy_range {
+                if dx * dx + dy * dy <= rsqr {
+                    let px = x + dx;
+                    let py = y + dy;
+                    self.draw_pixel(px, py, color);
+                }
+            }
+        }
+    }
+
+    /// Draw `text` with its top left corner at `x`, `y`, each pixel of the font becoming a `scale` wide square
+    fn draw_text(&mut self, x: isize, y: isize, text: &str, scale: usize, color: &Color) {
+        for (line_index, line) in text.lines().enumerate() {
+            let line_y = y + (line_index * LINE_HEIGHT * scale) as isize;
+            for (character_index, character) in line.chars().enumerate() {
+                let character_x = x + (character_index * ADVANCE * scale) as isize;
+                for (row_index, row) in font::glyph(character).iter().enumerate() {
+                    for column in 0..GLYPH_WIDTH {
+                        if row & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                            self.draw_square(
+                                character_x + (column * scale) as isize,
+                                line_y + (row_index * scale) as isize,
+                                scale,
+                                scale,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self);
+
+    /// Give the screen back once drawing is over, before anything else is printed
+    fn release(&mut self) {}
+
+    /// Keyboard and mouse events of surfaces which also receive input, such as terminals
+    fn input_events(&mut self) -> Vec<InputEvent> {
+        vec![]
+    }
+
+    /// Whether the mouse pointer is left to the renderer, nothing else showing it on this surface
+    fn draws_cursor(&self) -> bool {
+        false
+    }
+
+    /// Copy of the pixels within a rectangle, for `restore` to take off an overlay drawn over them once presented.
+    /// Surfaces which cannot read their pixels back have nothing to keep.
+    fn save(&self, x: isize, y: isize, width: usize, height: usize) -> Option<SavedRectangle> {
+        let _ = (x, y, width, height);
+        None
+    }
+
+    fn restore(&mut self, saved: &SavedRectangle) {
+        let _ = saved;
+    }
+}
+
+/// Pixels of the part of a rectangle within a surface, in the surface's own format
+pub struct SavedRectangle {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    bytes: Vec<u8>,
+}
+
+impl SavedRectangle {
+    /// Copy the part within a `surface_width` wide surface of the rectangle, from a buffer of `line_length` bytes long
+    /// lines
+    pub fn copy(
+        buffer: &[u8],
+        (surface_width, line_length, bytes_per_pixel): (usize, usize, usize),
+        x: isize,
+        y: isize,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        let surface_height = buffer.len() / line_length.max(1);
+        let clip = |start: isize, length: usize, size: usize| {
+            let end = start
+                .saturating_add(length.min(isize::MAX as usize) as isize)
+                .clamp(0, size as isize);
+            let start = start.clamp(0, size as isize);
+            (start as usize, (end - start) as usize)
+        };
+        let (x, width) = clip(x, width, surface_width);
+        let (y, height) = clip(y, height, surface_height);
+        let mut bytes = Vec::with_capacity(width * height * bytes_per_pixel);
+        for row in y..y + height {
+            let start = row * line_length + x * bytes_per_pixel;
+            bytes.extend_from_slice(&buffer[start..start + width * bytes_per_pixel]);
+        }
+        SavedRectangle {
+            x,
+            y,
+            width,
+            height,
+            bytes,
+        }
+    }
+
+    /// Put the pixels back, unless the buffer was resized since and no longer holds the rectangle
+    pub fn paste(&self, buffer: &mut [u8], line_length: usize, bytes_per_pixel: usize) {
+        let row_length = self.width * bytes_per_pixel;
+        if self.x * bytes_per_pixel + row_length > line_length || (self.y + self.height) * line_length > buffer.len() {
+            return;
+        }
+        for (row, bytes) in (self.y..self.y + self.height).zip(self.bytes.chunks_exact(row_length.max(1))) {
+            let start = row * line_length + self.x * bytes_per_pixel;
+            buffer[start..start + row_length].copy_from_slice(bytes);
+        }
+    }
+}
+
+/// RGBA image kept in memory, usable without any display
+pub struct MemoryBuffer {
+    width: usize,
+    height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// `decay` as a multiplier out of 256, so that channels can be faded with integer operations only. It stays below 256
+/// for every lit channel to eventually go dark.
+pub fn fade_factor(decay: f64) -> u32 {
+    (decay.clamp(0f64, 1f64) * 255f64) as u32
+}
+
+impl MemoryBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        MemoryBuffer {
+            width,
+            height,
+            pixels: vec![0; width * height * BYTES_PER_PIXEL],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let index = (y * self.width + x) * BYTES_PER_PIXEL;
+        self.pixels[index..index + BYTES_PER_PIXEL].try_into().unwrap()
+    }
+}
+
+impl RenderTarget for MemoryBuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn clear(&mut self) {
+        self.pixels.fill(0);
+    }
+
+    fn fade(&mut self, decay: f64) {
+        let factor = fade_factor(decay);
+        for pixel in self.pixels.chunks_exact_mut(BYTES_PER_PIXEL) {
+            for channel in &mut pixel[..3] {
+                *channel = ((*channel as u32 * factor) >> 8) as u8;
+            }
+        }
+    }
+
+    fn draw_pixel(&mut self, x: isize, y: isize, color: &Color) {
+        if x < 0 || x >= self.width as isize || y < 0 || y >= self.height as isize {
+            return;
+        }
+        let anchor_pixel_index = (y as usize * self.width + x as usize) * BYTES_PER_PIXEL;
+        self.pixels[anchor_pixel_index..anchor_pixel_index + BYTES_PER_PIXEL].copy_from_slice(color);
+    }
+
+    fn draw(&mut self) {}
+
+    fn save(&self, x: isize, y: isize, width: usize, height: usize) -> Option<SavedRectangle> {
+        let layout = (self.width, self.width * BYTES_PER_PIXEL, BYTES_PER_PIXEL);
+        Some(SavedRectangle::copy(&self.pixels, layout, x, y, width, height))
+    }
+
+    fn restore(&mut self, saved: &SavedRectangle) {
+        saved.paste(&mut self.pixels, self.width * BYTES_PER_PIXEL, BYTES_PER_PIXEL);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::render::{MemoryBuffer, RenderTarget};
+
+    #[test]
+    fn test_memory_buffer() {
+        let mut buffer = MemoryBuffer::new(10, 10);
+        let color = [255, 0, 0, 255];
+        buffer.draw_circle(0, 0, 2, &color);
+        buffer.draw();
+
+        let drawn_pixels = buffer.pixels.chunks_exact(4).filter(|pixel| *pixel == color).count();
+        // Only the quarter of the circle within the buffer is drawn
+        assert_eq!(drawn_pixels, 6);
+        assert_eq!(buffer.pixel(2, 0), color);
+        assert_eq!(buffer.pixel(2, 1), [0, 0, 0, 0]);
+
+        // Shapes far larger than the buffer are clipped to it rather than visited pixel by pixel
+        buffer.draw_circle(-1_000_000_000, 5, usize::MAX, &color);
+        assert!(buffer.pixels.chunks_exact(4).all(|pixel| pixel == color));
+        buffer.clear();
+        buffer.draw_square(-5, 8, usize::MAX, usize::MAX, &color);
+        assert_eq!(
+            buffer.pixels.chunks_exact(4).filter(|pixel| *pixel == color).count(),
+            20
+        );
+        buffer.draw_circle(isize::MAX, isize::MAX, usize::MAX, &color);
+        buffer.clear();
+        buffer.draw_circle(0, 0, 2, &color);
+
+        buffer.fade(0.5);
+        assert_eq!(buffer.pixel(2, 0), [126, 0, 0, 255]);
+
+        buffer.clear();
+        assert!(buffer.pixels.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_saved_rectangle() {
+        let mut buffer = MemoryBuffer::new(10, 10);
+        let color = [255, 0, 0, 255];
+        buffer.draw_circle(5, 5, 4, &color);
+        let before = buffer.pixels.clone();
+
+        // Only the part within the buffer is kept
+        let saved = buffer.save(-3, 6, 6, 100).unwrap();
+        assert_eq!((saved.x, saved.y, saved.width, saved.height), (0, 6, 3, 4));
+        buffer.draw_square(-3, 6, 6, 100, &[0, 255, 0, 255]);
+        buffer.restore(&saved);
+        assert_eq!(buffer.pixels, before);
+
+        // Rectangles entirely outside of the buffer are empty
+        let saved = buffer.save(20, 20, 5, 5).unwrap();
+        assert_eq!((saved.width, saved.height), (0, 0));
+        buffer.restore(&saved);
+        assert_eq!(buffer.pixels, before);
+    }
+}
@@ -0,0 +1,290 @@
+// Responsible for setting up what is simulated: a scenario, chosen by name with SCENARIO, builds the initial
+// population from the config and suggests how to look at it
+
+use crate::camera::Camera;
+use crate::config::Config;
+use crate::generators;
+use crate::physics::{Coordinates, DIMENSIONS, Particle, Population};
+use crate::random::SplitMix64;
+use rand::Rng;
+
+/// Names accepted by `from_name`
+pub const NAMES: [&str; 7] = [
+    "random-in-screen",
+    "three-body",
+    "plummer",
+    "cold-collapse",
+    "disk",
+    "kepler",
+    "galaxies",
+];
+
+/// Screen assumed by runs drawing nothing, such as benchmarks
+pub const DEFAULT_SCREEN_SIZE: (usize, usize) = (2560, 1440);
+
+pub trait Scenario {
+    /// Population of about `POP_SIZE` particles, laid out for a `width` by `height` screen unless the scenario has a
+    /// scale of its own
+    fn population(&self, config: &Config, width: usize, height: usize, rng: &mut SplitMix64) -> Population;
+
+    /// Distance from the origin within which the population starts, None if it is laid out for the screen
+    fn extent(&self) -> Option<f64> {
+        None
+    }
+
+    /// Camera of the config, zoomed so that the population fills a `width` by `height` screen
+    fn camera(&self, config: &Config, width: usize, height: usize) -> Camera {
+        let camera = config.camera(height);
+        match self.extent() {
+            Some(extent) => Camera {
+                zoom: width.min(height) as f64 / 2f64 / extent,
+                ..camera
+            },
+            None => camera,
+        }
+    }
+}
+
+pub fn from_name(name: &str) -> Option<Box<dyn Scenario>> {
+    match name {
+        "random-in-screen" => Some(Box::new(RandomInScreen)),
+        "three-body" => Some(Box::new(ThreeBody)),
+        "plummer" => Some(Box::new(Plummer)),
+        "cold-collapse" => Some(Box::new(ColdCollapse)),
+        "disk" => Some(Box::new(ExponentialDisk)),
+        "kepler" => Some(Box::new(Kepler)),
+        "galaxies" => Some(Box::new(CollidingGalaxies)),
+        _ => None,
+    }
+}
+
+/// Particles at rest, uniformly spread over a `width` by `height` rectangle centered on the origin, and over 200
+/// along the other axes
+pub fn random_in_screen(pop_size: usize, width: u32, height: u32, mass: f64, rng: &mut impl Rng) -> Population {
+    let half_width = width as f64 / 2f64;
+    let half_height = height as f64 / 2f64;
+    (0..pop_size)
+        .map(|_| {
+            let mut position = Coordinates::default();
+            position[0] = rng.random_range((-half_width)..half_width);
+            position[1] = rng.random_range((-half_height)..half_height);
+            for i in (0..DIMENSIONS).skip(2) {
+                position[i] = rng.random_range(-100.0..100.0);
+            }
+            Particle {
+                mass,
+                speed: Coordinates::default(),
+                position,
+            }
+        })
+        .collect()
+}
+
+/// Three particles at rest on the corners of a square, whose trajectories are checked by the e2e-test build
+pub fn three_body() -> Population {
+    [
+        (15f64, 100f64, 100f64),
+        (10f64, 100f64, -100f64),
+        (10f64, -100f64, -100f64),
+    ]
+    .into_iter()
+    .map(|(mass, x, y)| {
+        let mut position = Coordinates::default();
+        position[0] = x;
+        position[1] = y;
+        Particle {
+            mass,
+            speed: Coordinates::default(),
+            position,
+        }
+    })
+    .collect()
+}
+
+/// `POP_SIZE` particles of `DEFAULT_PARTICLE_MASS` at rest over the screen
+pub struct RandomInScreen;
+
+impl Scenario for RandomInScreen {
+    fn population(&self, config: &Config, width: usize, height: usize, rng: &mut SplitMix64) -> Population {
+        random_in_screen(
+            config.pop_size,
+            width as u32,
+            height as u32,
+            config.default_particle_mass,
+            rng,
+        )
+    }
+}
+
+/// See `three_body`
+pub struct ThreeBody;
+
+impl Scenario for ThreeBody {
+    fn population(&self, _config: &Config, _width: usize, _height: usize, _rng: &mut SplitMix64) -> Population {
+        three_body()
+    }
+}
+
+// Mass of the whole population when each particle has the default mass
+fn total_mass(config: &Config) -> f64 {
+    config.pop_size as f64 * config.default_particle_mass
+}
+
+/// Cluster in virial equilibrium, see `generators::plummer`
+pub struct Plummer;
+
+const PLUMMER_SCALE_RADIUS: f64 = 100f64;
+
+impl Scenario for Plummer {
+    fn population(&self, config: &Config, _width: usize, _height: usize, rng: &mut SplitMix64) -> Population {
+        generators::plummer(config.pop_size, total_mass(config), PLUMMER_SCALE_RADIUS, config.g, rng)
+    }
+
+    fn extent(&self) -> Option<f64> {
+        Some(3f64 * PLUMMER_SCALE_RADIUS)
+    }
+}
+
+/// Ball of particles at rest, see `generators::cold_collapse`
+pub struct ColdCollapse;
+
+const COLD_COLLAPSE_RADIUS: f64 = 300f64;
+
+impl Scenario for ColdCollapse {
+    fn population(&self, config: &Config, _width: usize, _height: usize, rng: &mut SplitMix64) -> Population {
+        generators::cold_collapse(config.pop_size, total_mass(config), COLD_COLLAPSE_RADIUS, rng)
+    }
+
+    fn extent(&self) -> Option<f64> {
+        Some(COLD_COLLAPSE_RADIUS)
+    }
+}
+
+// Disk of the particles but one, which is the central mass ten times heavier than the rest of the disk
+fn disk(particles: usize, config: &Config, scale_length: f64) -> generators::Disk {
+    let mass = particles as f64 * config.default_particle_mass;
+    generators::Disk {
+        particles,
+        mass,
+        scale_length,
+        central_mass: 10f64 * mass,
+    }
+}
+
+/// Galaxy around a central mass, see `generators::exponential_disk`
+pub struct ExponentialDisk;
+
+const DISK_SCALE_LENGTH: f64 = 80f64;
+
+impl Scenario for ExponentialDisk {
+    fn population(&self, config: &Config, _width: usize, _height: usize, rng: &mut SplitMix64) -> Population {
+        let disk = disk(config.pop_size.saturating_sub(1).max(1), config, DISK_SCALE_LENGTH);
+        generators::exponential_disk(&disk, config.g, rng)
+    }
+
+    fn extent(&self) -> Option<f64> {
+        Some(5f64 * DISK_SCALE_LENGTH)
+    }
+}
+
+/// Planets of `DEFAULT_PARTICLE_MASS` around a star a thousand times heavier, see `generators::kepler`
+pub struct Kepler;
+
+const KEPLER_INNER_RADIUS: f64 = 50f64;
+const KEPLER_OUTER_RADIUS: f64 = 400f64;
+
+impl Scenario for Kepler {
+    fn population(&self, config: &Config, _width: usize, _height: usize, rng: &mut SplitMix64) -> Population {
+        generators::kepler(
+            config.pop_size.saturating_sub(1).max(1),
+            1000f64 * config.default_particle_mass,
+            config.default_particle_mass,
+            KEPLER_INNER_RADIUS,
+            KEPLER_OUTER_RADIUS,
+            config.g,
+            rng,
+        )
+    }
+
+    fn extent(&self) -> Option<f64> {
+        Some(KEPLER_OUTER_RADIUS)
+    }
+}
+
+/// Two galaxies on a parabolic encounter, see `generators::colliding_galaxies`
+pub struct CollidingGalaxies;
+
+const GALAXIES_SCALE_LENGTH: f64 = 50f64;
+const GALAXIES_SEPARATION: f64 = 600f64;
+
+impl Scenario for CollidingGalaxies {
+    fn population(&self, config: &Config, _width: usize, _height: usize, rng: &mut SplitMix64) -> Population {
+        let disk = disk(
+            (config.pop_size / 2).saturating_sub(1).max(1),
+            config,
+            GALAXIES_SCALE_LENGTH,
+        );
+        // By default just enough for the galaxies to escape each other if they were point masses
+        let total_mass = 2f64 * (disk.mass + disk.central_mass);
+        let relative_speed = config
+            .galaxies_relative_speed
+            .unwrap_or_else(|| (2f64 * config.g * total_mass / GALAXIES_SEPARATION).sqrt());
+        generators::colliding_galaxies(&disk, GALAXIES_SEPARATION, relative_speed, config.g, rng)
+    }
+
+    fn extent(&self) -> Option<f64> {
+        Some(GALAXIES_SEPARATION / 2f64 + 3f64 * GALAXIES_SCALE_LENGTH)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::config::Config;
+    use crate::physics::Particle;
+    use crate::physics::diagnostics::Diagnostics;
+    use crate::physics::test::coordinates;
+    use crate::random::SplitMix64;
+    use crate::scenario::{NAMES, from_name, three_body};
+
+    #[test]
+    fn test_scenarios() {
+        let config = Config {
+            pop_size: 50,
+            ..Config::default()
+        };
+        for name in NAMES {
+            let scenario = from_name(name).unwrap();
+            let population = scenario.population(&config, 800, 600, &mut SplitMix64::new(0));
+            assert_eq!(population.len(), if name == "three-body" { 3 } else { 50 }, "{name}");
+        }
+        assert!(from_name("big-bang").is_none());
+
+        assert_eq!(three_body()[1].position, coordinates([100f64, -100f64]));
+        let kepler = from_name("kepler").unwrap();
+        assert_eq!(kepler.camera(&config, 800, 600).zoom, 300f64 / 400f64);
+        assert_eq!(
+            from_name("random-in-screen").unwrap().camera(&config, 800, 600).zoom,
+            1f64
+        );
+    }
+
+    #[test]
+    fn test_colliding_galaxies() {
+        let config = Config {
+            pop_size: 50,
+            galaxies_relative_speed: Some(3f64),
+            ..Config::default()
+        };
+        let gravity = config.gravity();
+        let population = from_name("galaxies")
+            .unwrap()
+            .population(&config, 800, 600, &mut SplitMix64::new(0));
+        // Each galaxy is one half of the population, the first one starting on the left
+        let center_of_mass_speed = |galaxy: &[Particle]| {
+            let diagnostics = Diagnostics::new(&galaxy.to_vec(), &gravity);
+            diagnostics.momentum[0] / diagnostics.mass
+        };
+        let (left, right) = population.split_at(population.len() / 2);
+        assert!((center_of_mass_speed(left) - center_of_mass_speed(right) - 3f64).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,171 @@
+// Responsible for saving a population to a file and loading it back, so that a simulation can be resumed later or
+// shared. All numbers are stored in little endian, after a header describing how the population was simulated:
+//
+// magic "RENGINE\0" | version u32 | DIMENSIONS u32 | G f64 | MINIMAL_DISTANCE f64 | step u64 | particles count u64
+//
+// Each particle then takes DIMENSIONS * 2 + 1 f64: mass, speed then position.
+
+use crate::physics::{Coordinates, DIMENSIONS, Gravity, Particle, Population};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"RENGINE\0";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// Number of steps simulated before the snapshot was taken
+    pub step: u64,
+    pub population: Population,
+}
+
+/// Start from the snapshot at `path` if there is one, or else from `new_population` at step 0
+pub fn load_or(path: Option<&Path>, gravity: &Gravity, new_population: impl FnOnce() -> Population) -> Snapshot {
+    match path {
+        Some(path) => {
+            load(path, gravity).unwrap_or_else(|err| panic!("Unable to load snapshot {}: {err}", path.display()))
+        }
+        None => Snapshot {
+            step: 0,
+            population: new_population(),
+        },
+    }
+}
+
+/// Save to `path` if there is one
+pub fn save_if_requested(path: Option<&Path>, gravity: &Gravity, population: &Population, step: u64) {
+    if let Some(path) = path {
+        save(path, gravity, population, step)
+            .unwrap_or_else(|err| panic!("Unable to save snapshot {}: {err}", path.display()));
+    }
+}
+
+pub fn save(path: &Path, gravity: &Gravity, population: &Population, step: u64) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer, gravity, population, step)?;
+    writer.flush()
+}
+
+pub fn load(path: &Path, gravity: &Gravity) -> Result<Snapshot> {
+    read(&mut BufReader::new(File::open(path)?), gravity)
+}
+
+pub fn write(writer: &mut impl Write, gravity: &Gravity, population: &Population, step: u64) -> Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(DIMENSIONS as u32).to_le_bytes())?;
+    writer.write_all(&gravity.g.to_le_bytes())?;
+    writer.write_all(&gravity.minimal_distance.to_le_bytes())?;
+    writer.write_all(&step.to_le_bytes())?;
+    writer.write_all(&(population.len() as u64).to_le_bytes())?;
+    for particle in population.iter() {
+        writer.write_all(&particle.mass.to_le_bytes())?;
+        for coordinates in [particle.speed, particle.position] {
+            for i in 0..DIMENSIONS {
+                writer.write_all(&coordinates[i].to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read a snapshot, failing if it was not written by a build simulating the same `gravity`
+pub fn read(reader: &mut impl Read, gravity: &Gravity) -> Result<Snapshot> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a rengine snapshot".to_string()));
+    }
+    check_header("version", read_u32(reader)?, VERSION)?;
+    check_header("DIMENSIONS", read_u32(reader)?, DIMENSIONS as u32)?;
+    check_header("G", read_f64(reader)?, gravity.g)?;
+    check_header("MINIMAL_DISTANCE", read_f64(reader)?, gravity.minimal_distance)?;
+    let step = read_u64(reader)?;
+    let particles_count = read_u64(reader)?;
+
+    let mut population = Vec::new();
+    for _ in 0..particles_count {
+        let mass = read_f64(reader)?;
+        let mut speed = Coordinates::default();
+        let mut position = Coordinates::default();
+        for coordinates in [&mut speed, &mut position] {
+            for i in 0..DIMENSIONS {
+                coordinates[i] = read_f64(reader)?;
+            }
+        }
+        population.push(Particle { mass, speed, position });
+    }
+    Ok(Snapshot { step, population })
+}
+
+fn check_header<T: PartialEq + std::fmt::Display>(name: &str, found: T, expected: T) -> Result<()> {
+    if found == expected {
+        Ok(())
+    } else {
+        Err(invalid_data(format!(
+            "snapshot has {name} {found} but this simulation uses {expected}"
+        )))
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64(reader: &mut impl Read) -> Result<f64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::physics::{Gravity, Solver};
+    use crate::random::SplitMix64;
+    use crate::scenario;
+    use crate::snapshot::{Snapshot, read, write};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let population = scenario::random_in_screen(10, 2560, 1440, 10f64, &mut SplitMix64::new(0));
+        let mut gravity = Gravity {
+            g: 1f64,
+            minimal_distance: 0f64,
+            solver: Solver::BruteForce,
+        };
+        let mut bytes = Vec::new();
+        write(&mut bytes, &gravity, &population, 42).unwrap();
+        assert_eq!(
+            read(&mut bytes.as_slice(), &gravity).unwrap(),
+            Snapshot { step: 42, population }
+        );
+
+        gravity.g = 2f64;
+        assert_eq!(
+            read(&mut bytes.as_slice(), &gravity).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        gravity.g = 1f64;
+
+        // Corrupt the DIMENSIONS field of the header
+        bytes[12] += 1;
+        assert_eq!(
+            read(&mut bytes.as_slice(), &gravity).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,344 @@
+// Responsible for drawing into a terminal with 24-bit ANSI colors, for instance over SSH where there is no framebuffer
+// device. Keys typed in the terminal are translated to the events a Linux keyboard would send, and mouse wheel reports
+// to the ones of a Linux mouse, so that the same controls work everywhere.
+
+use crate::input::InputEvent;
+use crate::render::{Color, MemoryBuffer, RenderTarget, SavedRectangle};
+use std::io::{Read, Write, stdin, stdout};
+use std::mem::zeroed;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TerminalCharacters {
+    /// 2x4 dots per character, sharing a single color
+    Braille,
+    /// 1x2 pixels per character, each with its own color
+    HalfBlock,
+}
+
+impl TerminalCharacters {
+    /// Pixels drawn by a single character, horizontally and vertically
+    fn cell_size(&self) -> (usize, usize) {
+        match self {
+            TerminalCharacters::Braille => (2, 4),
+            TerminalCharacters::HalfBlock => (1, 2),
+        }
+    }
+}
+
+pub struct Terminal {
+    buffer: MemoryBuffer,
+    characters: TerminalCharacters,
+    columns: usize,
+    rows: usize,
+    // Settings of the terminal before it was switched to raw mode, None if stdin is not a terminal
+    original_termios: Option<libc::termios>,
+    released: bool,
+}
+
+impl Terminal {
+    pub fn new(characters: TerminalCharacters) -> Self {
+        let original_termios = unsafe {
+            let mut termios: libc::termios = zeroed();
+            (libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0).then_some(termios)
+        };
+        if let Some(original_termios) = original_termios {
+            // Keys are read one by one without waiting, and without echo nor signals so that Ctrl+C quits like Q.
+            // Output processing is kept so that stats printed at the end still start at the beginning of lines.
+            let mut raw = original_termios;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) };
+        }
+        // Hide the cursor, clear the screen and report mouse buttons, including the wheel, as SGR sequences
+        print!("\x1b[?25l\x1b[2J\x1b[?1000h\x1b[?1006h");
+
+        let (columns, rows) = terminal_size();
+        let (cell_width, cell_height) = characters.cell_size();
+        Terminal {
+            buffer: MemoryBuffer::new(columns * cell_width, rows * cell_height),
+            characters,
+            columns,
+            rows,
+            original_termios,
+            released: false,
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+// Columns and rows available for drawing, the last row being kept for the cursor to rest on
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } < 0 || size.ws_col == 0 {
+        (80, 23)
+    } else {
+        (size.ws_col as usize, (size.ws_row as usize).saturating_sub(1).max(1))
+    }
+}
+
+/// Escape sequences and characters drawing `buffer` over `rows` rows of `columns` characters
+pub fn encode_frame(buffer: &MemoryBuffer, characters: TerminalCharacters, columns: usize, rows: usize) -> String {
+    let (cell_width, cell_height) = characters.cell_size();
+    let pixel = |x: usize, y: usize| -> Color {
+        if x < buffer.width() && y < buffer.height() {
+            buffer.pixel(x, y)
+        } else {
+            [0, 0, 0, 0]
+        }
+    };
+    let mut frame = String::new();
+    let mut foreground = None;
+    let mut background = None;
+    for row in 0..rows {
+        frame += &format!("\x1b[{};1H", row + 1);
+        for column in 0..columns {
+            let (x, y) = (column * cell_width, row * cell_height);
+            match characters {
+                TerminalCharacters::Braille => {
+                    // Bit of each dot, see the Unicode braille patterns block
+                    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+                    let mut dots = 0;
+                    let mut sum = [0u32; 3];
+                    for (dy, dots_row) in DOTS.iter().enumerate() {
+                        for (dx, dot) in dots_row.iter().enumerate() {
+                            let color = pixel(x + dx, y + dy);
+                            if color[..3] != [0, 0, 0] {
+                                dots |= dot;
+                                for i in 0..3 {
+                                    sum[i] += color[i] as u32;
+                                }
+                            }
+                        }
+                    }
+                    if dots == 0 {
+                        frame.push(' ');
+                        continue;
+                    }
+                    let lit = dots.count_ones();
+                    let color = [sum[0] / lit, sum[1] / lit, sum[2] / lit];
+                    if foreground != Some(color) {
+                        frame += &format!("\x1b[38;2;{};{};{}m", color[0], color[1], color[2]);
+                        foreground = Some(color);
+                    }
+                    frame.push(char::from_u32(0x2800 + dots).unwrap());
+                }
+                TerminalCharacters::HalfBlock => {
+                    let top = pixel(x, y);
+                    let bottom = pixel(x, y + 1);
+                    let top = [top[0] as u32, top[1] as u32, top[2] as u32];
+                    let bottom = [bottom[0] as u32, bottom[1] as u32, bottom[2] as u32];
+                    if foreground != Some(top) {
+                        frame += &format!("\x1b[38;2;{};{};{}m", top[0], top[1], top[2]);
+                        foreground = Some(top);
+                    }
+                    if background != Some(bottom) {
+                        frame += &format!("\x1b[48;2;{};{};{}m", bottom[0], bottom[1], bottom[2]);
+                        background = Some(bottom);
+                    }
+                    frame.push('▀');
+                }
+            }
+        }
+    }
+    // Leave the cursor on the row below the image, with the default colors
+    frame += &format!("\x1b[0m\x1b[{};1H", rows + 1);
+    frame
+}
+
+// Linux key code of the key typing `byte`, see linux/input-event-codes.h
+fn key_code(byte: u8) -> Option<u16> {
+    const LETTERS: [u16; 26] = [
+        30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38, 50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17, 45, 21, 44,
+    ];
+    const DIGITS: [u16; 10] = [11, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+    match byte {
+        b'a'..=b'z' => Some(LETTERS[(byte - b'a') as usize]),
+        b'A'..=b'Z' => Some(LETTERS[(byte - b'A') as usize]),
+        b'0'..=b'9' => Some(DIGITS[(byte - b'0') as usize]),
+        b'-' | b'_' => Some(12),
+        b'=' | b'+' => Some(13),
+        b'[' | b'{' => Some(26),
+        b']' | b'}' => Some(27),
+        b',' | b'<' => Some(51),
+        b'.' | b'>' => Some(52),
+        b' ' => Some(57),
+        b'\r' | b'\n' => Some(28),
+        0x1b => Some(1),
+        // Ctrl+C quits like Q
+        0x03 => Some(16),
+        _ => None,
+    }
+}
+
+fn input_event(type_: u16, code: u16, value: i32) -> InputEvent {
+    InputEvent {
+        time: [0, 0],
+        type_,
+        code,
+        value,
+    }
+}
+
+// Wheel motion of the SGR mouse report `ESC [ < button ; column ; row M` at the start of `bytes`, with its length.
+// Reports of other buttons have no motion.
+fn mouse_report(bytes: &[u8]) -> Option<(Option<i32>, usize)> {
+    let parameters = bytes.strip_prefix(b"\x1b[<")?;
+    let end = parameters.iter().position(|&byte| byte == b'M' || byte == b'm')?;
+    let button = parameters[..end].split(|&byte| byte == b';').next()?;
+    let motion = match button {
+        b"64" => Some(1),
+        b"65" => Some(-1),
+        _ => None,
+    };
+    Some((motion, 3 + end + 1))
+}
+
+/// Press and release events of the keys typed as `bytes`, and wheel events of the mouse reports among them
+pub fn translate_keys(bytes: &[u8]) -> Vec<InputEvent> {
+    let mut events = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some((motion, length)) = mouse_report(&bytes[i..]) {
+            events.extend(motion.map(|value| input_event(2, 8, value)));
+            i += length;
+            continue;
+        }
+        let (code, length) = match &bytes[i..] {
+            // Arrows
+            [0x1b, b'[', b'A', ..] => (103, 3),
+            [0x1b, b'[', b'B', ..] => (108, 3),
+            [0x1b, b'[', b'C', ..] => (106, 3),
+            [0x1b, b'[', b'D', ..] => (105, 3),
+            // Home, as sent by xterm and by the Linux console
+            [0x1b, b'[', b'H', ..] => (102, 3),
+            [0x1b, b'[', b'1', b'~', ..] => (102, 4),
+            _ => {
+                if let Some(code) = key_code(bytes[i]) {
+                    events.extend([1, 0].map(|value| input_event(1, code, value)));
+                }
+                i += 1;
+                continue;
+            }
+        };
+        events.extend([1, 0].map(|value| input_event(1, code, value)));
+        i += length;
+    }
+    events
+}
+
+impl RenderTarget for Terminal {
+    fn width(&self) -> usize {
+        self.buffer.width()
+    }
+
+    fn height(&self) -> usize {
+        self.buffer.height()
+    }
+
+    fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    fn fade(&mut self, decay: f64) {
+        self.buffer.fade(decay);
+    }
+
+    fn draw_pixel(&mut self, x: isize, y: isize, color: &Color) {
+        self.buffer.draw_pixel(x, y, color);
+    }
+
+    fn save(&self, x: isize, y: isize, width: usize, height: usize) -> Option<SavedRectangle> {
+        self.buffer.save(x, y, width, height)
+    }
+
+    fn restore(&mut self, saved: &SavedRectangle) {
+        self.buffer.restore(saved);
+    }
+
+    fn draw(&mut self) {
+        let frame = encode_frame(&self.buffer, self.characters, self.columns, self.rows);
+        let mut stdout = stdout().lock();
+        stdout
+            .write_all(frame.as_bytes())
+            .and_then(|_| stdout.flush())
+            .expect("Unable to write to the terminal");
+
+        // Follow the size of the terminal, the next frame being drawn at the new size
+        let (columns, rows) = terminal_size();
+        if (columns, rows) != (self.columns, self.rows) {
+            let (cell_width, cell_height) = self.characters.cell_size();
+            self.buffer = MemoryBuffer::new(columns * cell_width, rows * cell_height);
+            self.columns = columns;
+            self.rows = rows;
+            print!("\x1b[2J");
+        }
+    }
+
+    fn release(&mut self) {
+        if self.released {
+            return;
+        }
+        self.released = true;
+        if let Some(original_termios) = &self.original_termios {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original_termios) };
+        }
+        // Stop reporting the mouse and show the cursor again, as they were changed even if stdin is not a terminal
+        print!("\x1b[?1006l\x1b[?1000l\x1b[?25h");
+        let _ = stdout().flush();
+    }
+
+    fn input_events(&mut self) -> Vec<InputEvent> {
+        if self.original_termios.is_none() || self.released {
+            return vec![];
+        }
+        let mut bytes = Vec::new();
+        let mut buffer = [0u8; 64];
+        while let Ok(count @ 1..) = stdin().read(&mut buffer) {
+            bytes.extend_from_slice(&buffer[..count]);
+        }
+        translate_keys(&bytes)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::render::{MemoryBuffer, RenderTarget};
+    use crate::terminal::{TerminalCharacters, encode_frame, translate_keys};
+
+    #[test]
+    fn test_terminal() {
+        let mut buffer = MemoryBuffer::new(2, 4);
+        buffer.draw_pixel(0, 0, &[255, 0, 0, 255]);
+        buffer.draw_pixel(1, 3, &[0, 0, 255, 255]);
+        let frame = encode_frame(&buffer, TerminalCharacters::Braille, 1, 1);
+        assert_eq!(frame, "\x1b[1;1H\x1b[38;2;127;0;127m\u{2881}\x1b[0m\x1b[2;1H");
+
+        let events = translate_keys(b"q\x1b[Dx");
+        let codes: Vec<(u16, i32)> = events.iter().map(|event| (event.code, event.value)).collect();
+        assert_eq!(codes, [(16, 1), (16, 0), (105, 1), (105, 0), (45, 1), (45, 0)]);
+
+        // Rotating and resetting the camera
+        let events = translate_keys(b",.\x1b[H\x1b[1~");
+        let codes: Vec<u16> = events
+            .iter()
+            .filter(|event| event.value == 1)
+            .map(|event| event.code)
+            .collect();
+        assert_eq!(codes, [51, 52, 102, 102]);
+
+        // Wheel up then down, a left click being ignored
+        let events = translate_keys(b"\x1b[<64;10;5M\x1b[<0;1;1M\x1b[<65;10;5Mq");
+        let events: Vec<(u16, u16, i32)> = events
+            .iter()
+            .map(|event| (event.type_, event.code, event.value))
+            .collect();
+        assert_eq!(events, [(2, 8, 1), (2, 8, -1), (1, 16, 1), (1, 16, 0)]);
+    }
+}
@@ -0,0 +1,159 @@
+// Responsible for recording rendered frames as an uncompressed video stream that any encoder can read, for instance
+// `ffmpeg -i recording.y4m recording.mp4` or `ffmpeg -f image2pipe -c:v ppm -i recording.ppm recording.mp4`
+
+use crate::render::{Color, MemoryBuffer, RenderTarget, SavedRectangle};
+use std::fs::File;
+use std::io::{BufWriter, Write, stdout};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VideoFormat {
+    /// Binary PPM (P6) images written one after the other
+    Ppm,
+    /// YUV4MPEG2 stream with full resolution chroma
+    Y4m,
+}
+
+/// Render target writing every drawn frame to a video stream
+pub struct VideoRecorder {
+    buffer: MemoryBuffer,
+    writer: Box<dyn Write>,
+    format: VideoFormat,
+    fps: u32,
+    frames: u64,
+}
+
+impl VideoRecorder {
+    pub fn new(writer: Box<dyn Write>, format: VideoFormat, width: usize, height: usize, fps: u32) -> Self {
+        VideoRecorder {
+            buffer: MemoryBuffer::new(width, height),
+            writer,
+            format,
+            fps,
+            frames: 0,
+        }
+    }
+
+    /// Record to the file at `path`, or to stdout if `path` is `-`
+    pub fn create(path: &str, format: VideoFormat, width: usize, height: usize, fps: u32) -> Self {
+        let writer: Box<dyn Write> = if path == "-" {
+            Box::new(BufWriter::new(stdout()))
+        } else {
+            Box::new(BufWriter::new(
+                File::create(path).unwrap_or_else(|err| panic!("Unable to create {path}: {err}")),
+            ))
+        };
+        Self::new(writer, format, width, height, fps)
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    fn write_frame(&mut self) -> std::io::Result<()> {
+        let (width, height) = (self.buffer.width(), self.buffer.height());
+        let pixels = self.buffer.pixels.chunks_exact(4);
+        match self.format {
+            VideoFormat::Ppm => {
+                write!(self.writer, "P6\n{width} {height}\n255\n")?;
+                let rgb: Vec<u8> = pixels.flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+                self.writer.write_all(&rgb)?;
+            }
+            VideoFormat::Y4m => {
+                if self.frames == 0 {
+                    writeln!(self.writer, "YUV4MPEG2 W{width} H{height} F{}:1 Ip A1:1 C444", self.fps)?;
+                }
+                self.writer.write_all(b"FRAME\n")?;
+                let yuv: Vec<[u8; 3]> = pixels.map(|pixel| rgb_to_yuv(pixel[0], pixel[1], pixel[2])).collect();
+                for plane in 0..3 {
+                    let plane: Vec<u8> = yuv.iter().map(|yuv| yuv[plane]).collect();
+                    self.writer.write_all(&plane)?;
+                }
+            }
+        }
+        self.writer.flush()
+    }
+}
+
+// ITU-R BT.601 conversion to limited range YUV, in integer arithmetic
+fn rgb_to_yuv(red: u8, green: u8, blue: u8) -> [u8; 3] {
+    let (red, green, blue) = (red as i32, green as i32, blue as i32);
+    let y = ((66 * red + 129 * green + 25 * blue + 128) >> 8) + 16;
+    let u = ((-38 * red - 74 * green + 112 * blue + 128) >> 8) + 128;
+    let v = ((112 * red - 94 * green - 18 * blue + 128) >> 8) + 128;
+    [y as u8, u as u8, v as u8]
+}
+
+impl RenderTarget for VideoRecorder {
+    fn width(&self) -> usize {
+        self.buffer.width()
+    }
+
+    fn height(&self) -> usize {
+        self.buffer.height()
+    }
+
+    fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    fn fade(&mut self, decay: f64) {
+        self.buffer.fade(decay);
+    }
+
+    fn draw_pixel(&mut self, x: isize, y: isize, color: &Color) {
+        self.buffer.draw_pixel(x, y, color);
+    }
+
+    fn save(&self, x: isize, y: isize, width: usize, height: usize) -> Option<SavedRectangle> {
+        self.buffer.save(x, y, width, height)
+    }
+
+    fn restore(&mut self, saved: &SavedRectangle) {
+        self.buffer.restore(saved);
+    }
+
+    fn draw(&mut self) {
+        self.write_frame().expect("Unable to write video frame");
+        self.frames += 1;
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::render::RenderTarget;
+    use crate::video::{VideoFormat, VideoRecorder};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    // Writer whose content can still be read once handed to the recorder
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_video_recorder() {
+        let output = SharedBuffer::default();
+        let mut recorder = VideoRecorder::new(Box::new(output.clone()), VideoFormat::Y4m, 4, 2, 30);
+        recorder.draw_pixel(0, 0, &[255, 255, 255, 255]);
+        recorder.draw();
+        recorder.draw();
+
+        let header = b"YUV4MPEG2 W4 H2 F30:1 Ip A1:1 C444\n";
+        let frame_length = b"FRAME\n".len() + 4 * 2 * 3;
+        let bytes = output.0.lock().unwrap();
+        assert_eq!(recorder.frames(), 2);
+        assert_eq!(bytes.len(), header.len() + 2 * frame_length);
+        assert!(bytes.starts_with(header));
+        // White then black luma
+        assert_eq!(bytes[header.len() + 6..header.len() + 8], [235, 16]);
+    }
+}